@@ -0,0 +1,58 @@
+//! Benchmarks for the neighbor search, prediction and cross-validation hot
+//! paths, on a synthetic dataset. Set `BENCH_ROWS` to change its size
+//! (default 1000), e.g. `BENCH_ROWS=10000 cargo bench`.
+
+use std::env;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rusty_neighbors::data::Sample;
+use rusty_neighbors::distance::DistanceMetric;
+use rusty_neighbors::knn::{get_neighbors, k_nearest_neighbors, predict_classification, Voting};
+use rusty_neighbors::validation::evaluate_algorithm;
+
+const N_FEATURES: usize = 4;
+const N_CLASSES: usize = 3;
+const NUM_NEIGHBORS: usize = 5;
+
+/// `n_rows` random rows with Iris-like shape: four features, each class
+/// clustered around its own center.
+fn synthetic_dataset(n_rows: usize) -> Vec<Sample> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..n_rows)
+        .map(|i| {
+            let class = i % N_CLASSES;
+            let features = (0..N_FEATURES).map(|_| class as f64 + rng.gen_range(-1.0..1.0)).collect();
+            Sample::new(features, &format!("class_{}", class))
+        })
+        .collect()
+}
+
+fn bench_rows() -> usize {
+    env::var("BENCH_ROWS").ok().and_then(|rows| rows.parse().ok()).unwrap_or(1000)
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let n_rows = bench_rows();
+    let dataset = synthetic_dataset(n_rows);
+    let query = &dataset[0];
+
+    c.bench_function(&format!("get_neighbors/{}", n_rows), |b| {
+        b.iter(|| get_neighbors(black_box(&dataset), black_box(query), NUM_NEIGHBORS, DistanceMetric::Euclidean))
+    });
+
+    c.bench_function(&format!("predict_classification/{}", n_rows), |b| {
+        b.iter(|| predict_classification(black_box(&dataset), black_box(query), NUM_NEIGHBORS, DistanceMetric::Euclidean, Voting::Majority))
+    });
+
+    let mut group = c.benchmark_group("evaluate_algorithm");
+    // Each iteration predicts every row once, so fewer samples keep the run short.
+    group.sample_size(10);
+    group.bench_function(n_rows.to_string(), |b| {
+        b.iter(|| evaluate_algorithm(black_box(&dataset), k_nearest_neighbors, 5, NUM_NEIGHBORS, DistanceMetric::Euclidean, Voting::Majority, None))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use crate::data::Sample;
+use crate::distance::{distance, DistanceMetric};
+use crate::knn::{compare_distances, MeasuredFlower};
+
+/// Leaves at or below this size are scanned row by row instead of split.
+const LEAF_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Children {
+    /// Indices into `BallTree::points`.
+    Leaf(Vec<usize>),
+    Split(usize, usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node<L> {
+    /// Centroid of the node's points. Its class is ignored.
+    center: Sample<L>,
+    /// Distance from `center` to the node's farthest point.
+    radius: f64,
+    children: Children,
+}
+
+/// A ball tree over a training set. Each node is a ball around the centroid
+/// of its rows, and a whole node is skipped when even its nearest possible
+/// point, `distance(query, center) - radius`, is farther than the current
+/// k-th nearest neighbor. Unlike `KdTree` the bound doesn't depend on
+/// splitting along single axes, so it holds up better with many features.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BallTree<L = String> {
+    points: Vec<Sample<L>>,
+    nodes: Vec<Node<L>>,
+    root: Option<usize>,
+    metric: DistanceMetric,
+}
+
+impl BallTree {
+    /// Whether `metric` can be searched with a ball tree. Pruning relies on
+    /// the triangle inequality, so the metric must be a true metric.
+    pub fn supports(metric: DistanceMetric) -> bool {
+        match metric {
+            DistanceMetric::Euclidean
+            | DistanceMetric::Manhattan
+            | DistanceMetric::Chebyshev
+            | DistanceMetric::Canberra
+            | DistanceMetric::Hamming => true,
+            DistanceMetric::Minkowski(p) => p >= 1.0,
+            DistanceMetric::Cosine | DistanceMetric::BrayCurtis | DistanceMetric::Correlation => false,
+        }
+    }
+}
+
+impl<L: Clone> BallTree<L> {
+    /// Builds a tree over `points`.
+    ///
+    /// Panics if `BallTree::supports(metric)` is false.
+    pub fn new(points: Vec<Sample<L>>, metric: DistanceMetric) -> BallTree<L> {
+        assert!(BallTree::supports(metric), "{:?} cannot be searched with a ball tree", metric);
+        let mut tree = BallTree {
+            points,
+            nodes: Vec::new(),
+            root: None,
+            metric,
+        };
+        let mut indices: Vec<usize> = (0..tree.points.len()).collect();
+        tree.root = tree.build(&mut indices);
+        tree
+    }
+
+    fn build(&mut self, indices: &mut [usize]) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+        let center = self.centroid(indices);
+        let radius = indices.iter()
+            .map(|&i| distance(self.metric, &center, &self.points[i]))
+            .fold(0.0, f64::max);
+
+        let node = self.nodes.len();
+        if indices.len() <= LEAF_SIZE {
+            self.nodes.push(Node { center, radius, children: Children::Leaf(indices.to_vec()) });
+            return Some(node);
+        }
+        // Children are filled in once they are built.
+        self.nodes.push(Node { center, radius, children: Children::Split(0, 0) });
+
+        // Split at the median of the feature with the widest spread.
+        let axis = self.widest_axis(indices);
+        let mid = indices.len() / 2;
+        let points = &self.points;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            compare_distances(points[a].features[axis], points[b].features[axis])
+        });
+        let (left, right) = indices.split_at_mut(mid);
+        // Both halves are non-empty since the node is bigger than a leaf.
+        let left = self.build(left).expect("left half is non-empty");
+        let right = self.build(right).expect("right half is non-empty");
+        self.nodes[node].children = Children::Split(left, right);
+        Some(node)
+    }
+
+    fn centroid(&self, indices: &[usize]) -> Sample<L> {
+        let first = &self.points[indices[0]];
+        let mut features = vec![0.0; first.features.len()];
+        for &i in indices {
+            for (sum, value) in features.iter_mut().zip(&self.points[i].features) {
+                *sum += value;
+            }
+        }
+        for sum in features.iter_mut() {
+            *sum /= indices.len() as f64;
+        }
+        Sample { features, class: first.class.clone() }
+    }
+
+    fn widest_axis(&self, indices: &[usize]) -> usize {
+        let dims = self.points[indices[0]].features.len();
+        let spread = |axis: usize| {
+            let values = indices.iter().map(|&i| self.points[i].features[axis]);
+            values.clone().fold(f64::NEG_INFINITY, f64::max) - values.fold(f64::INFINITY, f64::min)
+        };
+        (0..dims)
+            .max_by(|&a, &b| spread(a).total_cmp(&spread(b)))
+            .unwrap_or(0)
+    }
+
+    /// The rows the tree was built from, in their original order.
+    pub fn points(&self) -> &[Sample<L>] {
+        &self.points
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// The `k` points closest to `query`, nearest first, matching what
+    /// `get_neighbors` returns for the same rows.
+    pub fn nearest(&self, query: &Sample<L>, k: usize) -> Vec<MeasuredFlower<L>> {
+        // (distance, point index), kept sorted and at most `k` long
+        let mut best = Vec::new();
+        if k > 0 {
+            self.search(self.root, query, k, &mut best);
+        }
+        best.into_iter()
+            .map(|(distance, i)| MeasuredFlower { distance, class: self.points[i].class.clone() })
+            .collect()
+    }
+
+    fn search(&self, node: Option<usize>, query: &Sample<L>, k: usize, best: &mut Vec<(f64, usize)>) {
+        let Some(node) = node else {
+            return;
+        };
+        let node = &self.nodes[node];
+        let nearest_possible = distance(self.metric, query, &node.center) - node.radius;
+        let worst = best.last().map_or(f64::INFINITY, |&(d, _)| d);
+        if best.len() == k && !worst.is_nan() && nearest_possible > worst {
+            return;
+        }
+
+        match node.children {
+            Children::Leaf(ref indices) => {
+                for &i in indices {
+                    let dist = distance(self.metric, query, &self.points[i]);
+                    let pos = best.partition_point(|&(d, _)| compare_distances(d, dist).is_le());
+                    if pos < k {
+                        best.insert(pos, (dist, i));
+                        best.truncate(k);
+                    }
+                }
+            }
+            Children::Split(left, right) => {
+                // Visit the closer ball first so the farther one is more
+                // likely to be pruned.
+                let to_left = distance(self.metric, query, &self.nodes[left].center);
+                let to_right = distance(self.metric, query, &self.nodes[right].center);
+                let (near, far) = if compare_distances(to_left, to_right).is_le() { (left, right) } else { (right, left) };
+                self.search(Some(near), query, k, best);
+                self.search(Some(far), query, k, best);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+    use crate::knn::get_neighbors;
+
+    /// Random rows labelled with their index.
+    fn random_rows(rng: &mut StdRng, n_rows: usize, n_features: usize) -> Vec<Sample<usize>> {
+        (0..n_rows)
+            .map(|i| Sample { features: (0..n_features).map(|_| rng.gen_range(-10.0..10.0)).collect(), class: i })
+            .collect()
+    }
+
+    fn summary(neighbors: &[MeasuredFlower<usize>]) -> Vec<(usize, f64)> {
+        neighbors.iter().map(|n| (n.class, n.distance)).collect()
+    }
+
+    #[test]
+    fn ball_tree_nearest_matches_get_neighbors_in_20_dimensions() {
+        let mut rng = StdRng::seed_from_u64(72);
+        let train = random_rows(&mut rng, 300, 20);
+        let queries = random_rows(&mut rng, 20, 20);
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Chebyshev, DistanceMetric::Canberra, DistanceMetric::Minkowski(3.0)] {
+            let tree = BallTree::new(train.clone(), metric);
+            for query in &queries {
+                for k in [1, 5, 17] {
+                    let expected = get_neighbors(&train, query, k, metric);
+                    assert_eq!(summary(&tree.nearest(query, k)), summary(&expected), "{:?}, k = {}", metric, k);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,593 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use serde::{Deserialize, Serialize};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use crate::error::KnnError;
+use crate::knn::KnnClassifier;
+
+/// A row of the Iris dataset.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Flower {
+    pub sepal_length: f64,
+    pub sepal_width: f64,
+    pub petal_length: f64,
+    pub petal_width: f64,
+    pub class: String,
+}
+
+impl Flower {
+    /// Builds a flower from its four measurements, in `rowify` order. The
+    /// prediction functions take `Sample`s, so convert with `.into()`, or
+    /// build a whole dataset at once with `dataset_from_rows`.
+    pub fn from_parts(features: [f64; 4], class: &str) -> Flower {
+        let [sepal_length, sepal_width, petal_length, petal_width] = features;
+        Flower {
+            sepal_length,
+            sepal_width,
+            petal_length,
+            petal_width,
+            class: class.to_string(),
+        }
+    }
+
+    pub fn rowify(&self) -> Vec<f64> {
+        vec!(self.sepal_length, self.sepal_width, self.petal_length, self.petal_width)
+    }
+    pub fn rand_flower() -> Flower {
+        let mut rng = rand::thread_rng();
+        let s: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+        Flower {
+            sepal_length: rng.gen_range(0.1..7.0),
+            sepal_width: rng.gen_range(0.1..7.0),
+            petal_length: rng.gen_range(0.1..7.0),
+            petal_width: rng.gen_range(0.1..7.0),
+            class: s,
+        }
+    }
+}
+
+/// One readable line, e.g.
+/// `sepal_length=5.1 sepal_width=3.5 petal_length=1.4 petal_width=0.2 class=Iris-setosa`.
+impl fmt::Display for Flower {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "sepal_length={} sepal_width={} petal_length={} petal_width={} class={}",
+            self.sepal_length, self.sepal_width, self.petal_length, self.petal_width, self.class
+        )
+    }
+}
+
+/// A numeric feature type: `f64`, or `f32` to halve the memory a large
+/// training set takes. Distances are computed in `f64` either way.
+pub trait Feature: Copy {
+    fn to_f64(self) -> f64;
+}
+
+impl Feature for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Feature for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// A labelled row with any number of numeric features. The label is a class
+/// name by default; regression uses `Sample<f64>` with the numeric target in
+/// `class`. Features are `f64` unless `F` says otherwise; the distance
+/// functions and `get_neighbors` accept `f32` samples too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample<L = String, F = f64> {
+    pub features: Vec<F>,
+    pub class: L,
+}
+
+/// A sample labelled with a class name, for classification.
+pub type ClassSample = Sample<String>;
+
+/// A sample labelled with a numeric target, for regression.
+pub type RegressionSample = Sample<f64>;
+
+/// One readable line naming the features as `write_predictions` does, e.g.
+/// `feature_1=5.1 feature_2=3.5 class=Iris-setosa`.
+impl<L: fmt::Display, F: fmt::Display> fmt::Display for Sample<L, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, value) in self.features.iter().enumerate() {
+            write!(f, "feature_{}={} ", i + 1, value)?;
+        }
+        write!(f, "class={}", self.class)
+    }
+}
+
+impl<L, F> Sample<L, F> {
+    pub fn rowify(&self) -> &[F] {
+        &self.features
+    }
+}
+
+impl<L> Sample<L> {
+    /// The same sample with its features narrowed to `f32`.
+    pub fn into_f32(self) -> Sample<L, f32> {
+        Sample {
+            features: self.features.into_iter().map(|value| value as f32).collect(),
+            class: self.class,
+        }
+    }
+}
+
+impl Sample {
+    pub fn new(features: Vec<f64>, class: &str) -> Sample {
+        Sample {
+            features,
+            class: class.to_string(),
+        }
+    }
+
+    /// Parses a CSV record whose last column is the class and whose other
+    /// columns are numeric features.
+    pub fn from_record(record: &csv::StringRecord) -> Result<Sample, Box<dyn Error>> {
+        let (features, class) = parse_record(record)?;
+        let mut values = Vec::new();
+        for value in features {
+            values.push(value.ok_or("missing feature value")?);
+        }
+        Ok(Sample::new(values, class))
+    }
+}
+
+/// Splits a record into its features, with blank cells as `None`, and its
+/// class.
+fn parse_record(record: &csv::StringRecord) -> Result<(Vec<Option<f64>>, &str), String> {
+    let fields: Vec<&str> = record.iter().collect();
+    let Some((class, features)) = fields.split_last() else {
+        return Err("empty CSV record".to_string());
+    };
+
+    let mut values = Vec::new();
+    for field in features {
+        let field = field.trim();
+        if field.is_empty() {
+            values.push(None);
+            continue;
+        }
+        let value: f64 = field.parse()
+            .map_err(|e| format!("invalid feature value {:?}: {}", field, e))?;
+        values.push(Some(value));
+    }
+    Ok((values, class.trim()))
+}
+
+impl From<Flower> for Sample {
+    fn from(flower: Flower) -> Sample {
+        Sample {
+            features: flower.rowify(),
+            class: flower.class,
+        }
+    }
+}
+
+/// Builds a dataset from in-memory `(features, class)` pairs, for data that
+/// doesn't come from a CSV.
+pub fn dataset_from_rows(rows: Vec<(Vec<f64>, String)>) -> Vec<Sample> {
+    rows.into_iter()
+        .map(|(features, class)| Sample { features, class })
+        .collect()
+}
+
+/// What to do with rows that have blank or unreadable cells.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MissingValues {
+    /// Fail the whole load.
+    #[default]
+    Error,
+    /// Drop the row and count it in `LoadedDataset::skipped`.
+    Skip,
+    /// Fill blank feature cells with the mean of that column over the rows
+    /// that have a value. Unparseable cells, and columns blank in every row,
+    /// are still an error.
+    ImputeMean,
+    /// Keep blank feature cells as NaN, e.g. for `knn_impute`. Unparseable
+    /// cells are still an error.
+    Keep,
+}
+
+/// Settings for `load_csv_with` and `load_csv_reader_with`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    pub has_headers: bool,
+    pub delimiter: u8,
+    pub missing: MissingValues,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            has_headers: true,
+            delimiter: b',',
+            missing: MissingValues::Error,
+        }
+    }
+}
+
+/// The rows read by `load_csv_with`, plus how many malformed rows were
+/// dropped under `MissingValues::Skip`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedDataset {
+    pub samples: Vec<Sample>,
+    pub skipped: usize,
+}
+
+/// Loads a CSV whose last column is the class and whose other columns are
+/// numeric features, so Iris files load unchanged. Set `has_headers` to false
+/// for files without a header row, and `delimiter` to e.g. `b'\t'` for TSV.
+///
+/// Paths ending in `.gz` are decompressed on the fly with the `gzip`
+/// feature, and rejected without it.
+pub fn load_csv(path: &str, has_headers: bool, delimiter: u8) -> Result<Vec<Sample>, KnnError> {
+    load_csv_reader(open_dataset(path)?, has_headers, delimiter)
+}
+
+/// Like `load_csv`, but reads from any source: stdin, a byte buffer, a socket.
+pub fn load_csv_reader<R: Read>(reader: R, has_headers: bool, delimiter: u8) -> Result<Vec<Sample>, KnnError> {
+    let options = LoadOptions {
+        has_headers,
+        delimiter,
+        ..LoadOptions::default()
+    };
+    Ok(load_csv_reader_with(reader, &options)?.samples)
+}
+
+/// Like `load_csv`, with `options` also controlling how malformed rows are
+/// handled.
+pub fn load_csv_with(path: &str, options: &LoadOptions) -> Result<LoadedDataset, KnnError> {
+    load_csv_reader_with(open_dataset(path)?, options)
+}
+
+/// Opens `path` for reading, through a gzip decoder if it ends in `.gz`.
+#[cfg(feature = "gzip")]
+fn open_dataset(path: &str) -> Result<Box<dyn Read>, KnnError> {
+    let file = File::open(path)?;
+    if path.ends_with(".gz") {
+        Ok(Box::new(flate2::read::GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Opens `path` for reading, through a gzip decoder if it ends in `.gz`.
+#[cfg(not(feature = "gzip"))]
+fn open_dataset(path: &str) -> Result<Box<dyn Read>, KnnError> {
+    if path.ends_with(".gz") {
+        let message = format!("{} is gzip-compressed; enable the `gzip` feature to load it", path);
+        return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, message).into());
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+/// Like `load_csv_with`, but reads from any source.
+pub fn load_csv_reader_with<R: Read>(reader: R, options: &LoadOptions) -> Result<LoadedDataset, KnnError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(options.has_headers)
+        .delimiter(options.delimiter)
+        .from_reader(reader);
+
+    let mut rows = Vec::new();
+    let mut skipped = 0;
+    // Where the data starts, for errors about whole columns.
+    let mut first_line = 0;
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) if options.missing == MissingValues::Skip && matches!(e.kind(), csv::ErrorKind::UnequalLengths { .. }) => {
+                skipped += 1;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let line = record.position().map_or(0, |p| p.line());
+
+        let (features, class) = match parse_record(&record) {
+            Ok(parsed) => parsed,
+            Err(_) if options.missing == MissingValues::Skip => {
+                skipped += 1;
+                continue;
+            }
+            Err(message) => return Err(KnnError::Parse { line, message }),
+        };
+        if features.contains(&None) {
+            match options.missing {
+                MissingValues::Error => {
+                    return Err(KnnError::Parse { line, message: "missing feature value".to_string() });
+                }
+                MissingValues::Skip => {
+                    skipped += 1;
+                    continue;
+                }
+                MissingValues::ImputeMean | MissingValues::Keep => {}
+            }
+        }
+        if rows.is_empty() {
+            first_line = line;
+        }
+        rows.push((features, class.to_string()));
+    }
+
+    // Column means over the cells that were present, for ImputeMean.
+    let n_features = rows.first().map_or(0, |(features, _)| features.len());
+    let mut means = vec![f64::NAN; n_features];
+    if options.missing == MissingValues::ImputeMean {
+        for (i, mean) in means.iter_mut().enumerate() {
+            let present: Vec<f64> = rows.iter().filter_map(|(features, _)| features[i]).collect();
+            if present.is_empty() {
+                let message = format!("feature column {} is blank in every row, so it has no mean to impute", i + 1);
+                return Err(KnnError::Parse { line: first_line, message });
+            }
+            *mean = present.iter().sum::<f64>() / present.len() as f64;
+        }
+    }
+
+    let samples = rows.into_iter()
+        .map(|(features, class)| Sample {
+            features: features.iter().zip(&means).map(|(value, mean)| value.unwrap_or(*mean)).collect(),
+            class,
+        })
+        .collect();
+    Ok(LoadedDataset { samples, skipped })
+}
+
+/// Writes each row's features and class followed by its `predicted_class`.
+/// The header names the features `feature_1`, `feature_2`, and so on.
+pub fn write_predictions(path: &str, rows: &[Sample], predicted: &[String]) -> Result<(), Box<dyn Error>> {
+    if rows.len() != predicted.len() {
+        return Err(format!("{} rows but {} predictions", rows.len(), predicted.len()).into());
+    }
+    let mut writer = csv::Writer::from_path(path)?;
+
+    let n_features = rows.first().map_or(0, |row| row.features.len());
+    writer.write_record(prediction_header(n_features))?;
+    for (row, prediction) in rows.iter().zip(predicted) {
+        writer.write_record(prediction_record(row, prediction))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Predicts each row of a CSV as it is read and writes it out in the
+/// `write_predictions` format, so only `model`'s training set is held in
+/// memory. The input is laid out as for `load_csv` with a header row and
+/// comma delimiters; no header is written if it has no rows.
+pub fn predict_csv_streaming<R: Read, W: Write>(model: &KnnClassifier, reader: R, writer: W) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut record = csv::StringRecord::new();
+    let mut wrote_header = false;
+
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |p| p.line());
+        let row = Sample::from_record(&record).map_err(|e| format!("line {}: {}", line, e))?;
+        let prediction = model.predict(&row).map_err(|e| format!("line {}: {}", line, e))?;
+
+        if !wrote_header {
+            writer.write_record(prediction_header(row.features.len()))?;
+            wrote_header = true;
+        }
+        writer.write_record(prediction_record(&row, &prediction))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn prediction_header(n_features: usize) -> Vec<String> {
+    let mut header: Vec<String> = (1..=n_features).map(|i| format!("feature_{}", i)).collect();
+    header.push("class".to_string());
+    header.push("predicted_class".to_string());
+    header
+}
+
+fn prediction_record(row: &Sample, prediction: &str) -> Vec<String> {
+    let mut record: Vec<String> = row.features.iter().map(|value| value.to_string()).collect();
+    record.push(row.class.clone());
+    record.push(prediction.to_string());
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{distance, DistanceMetric};
+    use crate::knn::{get_neighbors, predict_classification, KnnClassifier, Voting};
+
+    const IRIS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../iris.csv");
+
+    fn load_with(csv: &str, missing: MissingValues) -> Result<LoadedDataset, KnnError> {
+        let options = LoadOptions {
+            has_headers: false,
+            missing,
+            ..LoadOptions::default()
+        };
+        load_csv_reader_with(csv.as_bytes(), &options)
+    }
+
+    #[test]
+    fn datasets_built_in_code_predict() {
+        let mut train = dataset_from_rows(vec![
+            (vec![5.1, 3.5, 1.4, 0.2], "Iris-setosa".to_string()),
+            (vec![4.9, 3.0, 1.4, 0.2], "Iris-setosa".to_string()),
+            (vec![6.3, 3.3, 6.0, 2.5], "Iris-virginica".to_string()),
+        ]);
+        train.push(Flower::from_parts([5.8, 2.7, 5.1, 1.9], "Iris-virginica").into());
+        assert_eq!(train[3].features, [5.8, 2.7, 5.1, 1.9]);
+
+        let query: Sample = Flower::from_parts([5.0, 3.4, 1.5, 0.2], "unknown").into();
+        let predicted = predict_classification(&train, &query, 1, DistanceMetric::Euclidean, Voting::Majority).unwrap();
+        assert_eq!(predicted, "Iris-setosa");
+    }
+
+    #[test]
+    fn samples_and_flowers_format_on_one_line() {
+        let flower = Flower::from_parts([5.1, 3.5, 1.4, 0.2], "Iris-setosa");
+        assert_eq!(
+            flower.to_string(),
+            "sepal_length=5.1 sepal_width=3.5 petal_length=1.4 petal_width=0.2 class=Iris-setosa"
+        );
+        let sample: Sample = flower.into();
+        assert_eq!(sample.to_string(), "feature_1=5.1 feature_2=3.5 feature_3=1.4 feature_4=0.2 class=Iris-setosa");
+
+        let regression: RegressionSample = Sample { features: vec![2.0], class: 4.5 };
+        assert_eq!(regression.to_string(), "feature_1=2 class=4.5");
+    }
+
+    #[test]
+    fn each_missing_value_policy_handles_one_blank_cell() {
+        let csv = "1,2,a\n,4,a\n5,6,b\n";
+        assert!(matches!(load_with(csv, MissingValues::Error), Err(KnnError::Parse { line: 2, .. })));
+
+        let skipped = load_with(csv, MissingValues::Skip).unwrap();
+        assert_eq!((skipped.samples.len(), skipped.skipped), (2, 1));
+
+        let imputed = load_with(csv, MissingValues::ImputeMean).unwrap();
+        assert_eq!(imputed.samples[1].features, [3.0, 4.0]);
+
+        let kept = load_with(csv, MissingValues::Keep).unwrap();
+        assert!(kept.samples[1].features[0].is_nan());
+        assert_eq!(kept.samples[1].features[1], 4.0);
+    }
+
+    #[test]
+    fn impute_mean_rejects_a_column_blank_in_every_row() {
+        let result = load_with("1,,a\n2,,b\n", MissingValues::ImputeMean);
+        assert!(matches!(result, Err(KnnError::Parse { line: 1, .. })));
+    }
+
+    #[test]
+    fn samples_hold_any_number_of_features() {
+        let csv = "0,0,0,0,0,0,0,0,0,0,low\n1,1,1,1,1,1,1,1,1,1,low\n9,9,9,9,9,9,9,9,9,9,high\n8,9,8,9,8,9,8,9,8,9,high\n";
+        let train = load_csv_reader(csv.as_bytes(), false, b',').unwrap();
+        assert!(train.iter().all(|row| row.features.len() == 10));
+
+        let query = Sample::new(vec![7.0; 10], "unknown");
+        let predicted = predict_classification(&train, &query, 3, DistanceMetric::Euclidean, Voting::Majority).unwrap();
+        assert_eq!(predicted, "high");
+    }
+
+    /// A path in the system temp directory unique to this test process.
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("rusty_neighbors_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn load_csv_reads_a_semicolon_file_without_a_header() {
+        let path = temp_path("semicolons.csv");
+        std::fs::write(&path, "5.1;3.5;1.4;0.2;Iris-setosa\n6.3;3.3;6.0;2.5;Iris-virginica\n").unwrap();
+        let dataset = load_csv(&path, false, b';');
+        std::fs::remove_file(&path).unwrap();
+
+        let dataset = dataset.unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset[0], Sample::new(vec![5.1, 3.5, 1.4, 0.2], "Iris-setosa"));
+        assert_eq!(dataset[1].class, "Iris-virginica");
+    }
+
+    #[test]
+    fn load_csv_reader_parses_a_byte_literal() {
+        let bytes: &[u8] = b"sepal_length,sepal_width,class\n1.0,2.0,a\n3.0,4.0,b\n";
+        let dataset = load_csv_reader(bytes, true, b',').unwrap();
+        assert_eq!(dataset, [Sample::new(vec![1.0, 2.0], "a"), Sample::new(vec![3.0, 4.0], "b")]);
+    }
+
+    #[test]
+    fn write_predictions_round_trips_through_a_file() {
+        let path = temp_path("predictions.csv");
+        let rows = [Sample::new(vec![1.0, 2.5], "a"), Sample::new(vec![3.0, 4.0], "b")];
+        let predicted = ["a".to_string(), "a".to_string()];
+        write_predictions(&path, &rows, &predicted).unwrap();
+        let written = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written.unwrap(), "feature_1,feature_2,class,predicted_class\n1,2.5,a,a\n3,4,b,a\n");
+        let error = write_predictions(&path, &rows, &predicted[..1]).unwrap_err();
+        assert_eq!(error.to_string(), "2 rows but 1 predictions");
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn both_sample_aliases_search_for_neighbors() {
+        let classes: Vec<ClassSample> = vec![Sample::new(vec![0.0], "near"), Sample::new(vec![5.0], "far")];
+        let query: ClassSample = Sample::new(vec![1.0], "unknown");
+        let nearest = get_neighbors(&classes, &query, 1, DistanceMetric::Euclidean);
+        assert_eq!((nearest[0].class.as_str(), nearest[0].distance), ("near", 1.0));
+
+        let targets: Vec<RegressionSample> = vec![Sample { features: vec![0.0], class: 1.5 }, Sample { features: vec![5.0], class: 9.0 }];
+        let query: RegressionSample = Sample { features: vec![4.0], class: 0.0 };
+        let nearest = get_neighbors(&targets, &query, 2, DistanceMetric::Euclidean);
+        assert_eq!(nearest.iter().map(|n| n.class).collect::<Vec<_>>(), [9.0, 1.5]);
+    }
+
+    #[test]
+    fn predict_csv_streaming_writes_one_line_per_row() {
+        let mut model = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority);
+        model.fit(vec![Sample::new(vec![0.0, 0.0], "a"), Sample::new(vec![9.0, 9.0], "b")]).unwrap();
+        let input = "x,y,class\n1,0.5,a\n8,9,a\n";
+        let mut output = Vec::new();
+        predict_csv_streaming(&model, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "feature_1,feature_2,class,predicted_class\n1,0.5,a,a\n8,9,a,b\n");
+
+        let mut output = Vec::new();
+        let error = predict_csv_streaming(&model, "x,y,class\n1,oops,a\n".as_bytes(), &mut output).unwrap_err();
+        assert!(error.to_string().starts_with("line 2: "), "{}", error);
+    }
+
+    #[test]
+    fn f32_features_give_f64_distances_within_tolerance() {
+        let dataset = load_csv(IRIS, true, b',').unwrap();
+        let narrowed: Vec<Sample<String, f32>> = dataset.iter().cloned().map(Sample::into_f32).collect();
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Cosine] {
+            for i in (0..dataset.len()).step_by(7) {
+                let wide = distance(metric, &dataset[0], &dataset[i]);
+                let narrow = distance(metric, &narrowed[0], &narrowed[i]);
+                assert!((wide - narrow).abs() < 1e-5, "{:?}: {} vs {}", metric, wide, narrow);
+            }
+        }
+        let nearest = get_neighbors(&narrowed, &narrowed[60], 3, DistanceMetric::Euclidean);
+        assert!(nearest.iter().all(|n| n.class == "Iris-versicolor"));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_csv_loads_the_same_rows() {
+        use flate2::write::GzEncoder;
+
+        let path = temp_path("iris.csv.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+        encoder.write_all(&std::fs::read(IRIS).unwrap()).unwrap();
+        encoder.finish().unwrap();
+        let gzipped = load_csv(&path, true, b',');
+        std::fs::remove_file(&path).unwrap();
+
+        let plain = load_csv(IRIS, true, b',').unwrap();
+        assert_eq!(plain.len(), 150);
+        assert_eq!(gzipped.unwrap(), plain);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn gzipped_csv_needs_the_gzip_feature() {
+        let error = load_csv("iris.csv.gz", true, b',').unwrap_err();
+        assert!(error.to_string().contains("enable the `gzip` feature"), "{}", error);
+    }
+}
@@ -0,0 +1,176 @@
+use ndarray::{s, Array1, Array2, ArrayView1, Axis, Zip};
+use serde::{Deserialize, Serialize};
+use crate::data::Sample;
+use crate::distance::DistanceMetric;
+use crate::knn::{select_nearest, MeasuredFlower};
+
+/// A training set stored as one `ndarray` matrix with a row per sample, so
+/// the distances from a query to every row come from whole-array operations
+/// rather than one pair at a time. Finds the same neighbors as
+/// `get_neighbors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DenseIndex<L = String> {
+    features: Array2<f64>,
+    classes: Vec<L>,
+    metric: DistanceMetric,
+}
+
+impl<L: Clone> DenseIndex<L> {
+    /// Copies `points` into a matrix.
+    ///
+    /// Panics if the rows don't all have the same number of features.
+    pub fn new(points: &[Sample<L>], metric: DistanceMetric) -> DenseIndex<L> {
+        let n_features = points.first().map_or(0, |p| p.features.len());
+        let mut features = Array2::zeros((points.len(), n_features));
+        for (mut row, point) in features.rows_mut().into_iter().zip(points) {
+            assert_eq!(point.features.len(), n_features, "every row needs the same number of features");
+            row.assign(&ArrayView1::from(&point.features[..]));
+        }
+        DenseIndex {
+            features,
+            classes: points.iter().map(|p| p.class.clone()).collect(),
+            metric,
+        }
+    }
+
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// Number of features per row.
+    pub fn n_features(&self) -> usize {
+        self.features.ncols()
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Distance from `query` to every row, in row order. As with the scalar
+    /// distance functions, only the features both sides have are compared.
+    pub fn distances(&self, query: &Sample<L>) -> Array1<f64> {
+        let n = query.features.len().min(self.features.ncols());
+        let train = self.features.slice(s![.., ..n]);
+        let q = ArrayView1::from(&query.features[..n]);
+        let diff = || &train - &q;
+
+        match self.metric {
+            DistanceMetric::Euclidean => diff().mapv(|d| d * d).sum_axis(Axis(1)).mapv(f64::sqrt),
+            DistanceMetric::Manhattan => diff().mapv(f64::abs).sum_axis(Axis(1)),
+            DistanceMetric::Chebyshev => diff().mapv(f64::abs).fold_axis(Axis(1), 0.0, |&max, &d| max.max(d)),
+            DistanceMetric::Minkowski(p) => {
+                assert!(p > 0.0, "minkowski_distance requires p > 0, got {}", p);
+                diff().mapv(|d| d.abs().powf(p)).sum_axis(Axis(1)).mapv(|sum| sum.powf(1.0 / p))
+            }
+            DistanceMetric::Cosine => {
+                let dots = train.dot(&q);
+                let norms = train.map_axis(Axis(1), |row| row.dot(&row).sqrt());
+                let q_norm = q.dot(&q).sqrt();
+                // All-zero rows count as orthogonal, as in cosine_distance.
+                Zip::from(&dots).and(&norms).map_collect(|&dot, &norm| {
+                    if norm == 0.0 || q_norm == 0.0 { 1.0 } else { 1.0 - dot / (norm * q_norm) }
+                })
+            }
+            DistanceMetric::Canberra => {
+                let denominators = train.mapv(f64::abs) + q.mapv(f64::abs);
+                let mut terms = diff().mapv(f64::abs);
+                Zip::from(&mut terms).and(&denominators).for_each(|term, &denominator| {
+                    *term = if denominator == 0.0 { 0.0 } else { *term / denominator };
+                });
+                terms.sum_axis(Axis(1))
+            }
+            DistanceMetric::BrayCurtis => {
+                let differences = diff().mapv(f64::abs).sum_axis(Axis(1));
+                let totals = (&train + &q).mapv(f64::abs).sum_axis(Axis(1));
+                Zip::from(&differences).and(&totals).map_collect(|&difference, &total| {
+                    if total == 0.0 { 0.0 } else { difference / total }
+                })
+            }
+            DistanceMetric::Correlation => {
+                // Pearson correlation is the cosine of the mean-centered rows.
+                let means = train.mean_axis(Axis(1)).unwrap_or_else(|| Array1::zeros(train.nrows()));
+                let centered = &train - &means.insert_axis(Axis(1));
+                let q_centered = &q - q.mean().unwrap_or(0.0);
+                let covariances = centered.dot(&q_centered);
+                let deviations = centered.map_axis(Axis(1), |row| row.dot(&row).sqrt());
+                let q_deviation = q_centered.dot(&q_centered).sqrt();
+                // Constant rows count as uncorrelated, as in correlation_distance.
+                Zip::from(&covariances).and(&deviations).map_collect(|&covariance, &deviation| {
+                    if deviation == 0.0 || q_deviation == 0.0 { 1.0 } else { 1.0 - covariance / (deviation * q_deviation) }
+                })
+            }
+            DistanceMetric::Hamming => diff().mapv(|d| if d == 0.0 { 0.0 } else { 1.0 }).sum_axis(Axis(1)),
+        }
+    }
+
+    /// The `k` rows closest to `query`, nearest first.
+    pub fn nearest(&self, query: &Sample<L>, k: usize) -> Vec<MeasuredFlower<L>> {
+        let distances = self.distances(query)
+            .into_iter()
+            .zip(&self.classes)
+            .map(|(distance, class)| MeasuredFlower { distance, class: class.clone() })
+            .collect();
+        select_nearest(distances, k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+    use crate::distance::distance;
+    use crate::knn::get_neighbors;
+
+    /// Random rows labelled with their index, some with zero features so the
+    /// zero-denominator branches run too.
+    fn random_rows(rng: &mut StdRng, n_rows: usize, n_features: usize) -> Vec<Sample<usize>> {
+        (0..n_rows)
+            .map(|i| {
+                let features = (0..n_features)
+                    .map(|_| if rng.gen_bool(0.1) { 0.0 } else { rng.gen_range(-5.0..5.0) })
+                    .collect();
+                Sample { features, class: i }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dense_neighbors_match_get_neighbors_for_every_metric() {
+        let mut rng = StdRng::seed_from_u64(58);
+        let train = random_rows(&mut rng, 60, 5);
+        let queries = random_rows(&mut rng, 10, 5);
+        let metrics = [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Manhattan,
+            DistanceMetric::Chebyshev,
+            DistanceMetric::Minkowski(3.0),
+            DistanceMetric::Cosine,
+            DistanceMetric::Canberra,
+            DistanceMetric::BrayCurtis,
+            DistanceMetric::Correlation,
+            DistanceMetric::Hamming,
+        ];
+
+        for metric in metrics {
+            let index = DenseIndex::new(&train, metric);
+            for query in &queries {
+                for (dense, row) in index.distances(query).iter().zip(&train) {
+                    let scalar = distance(metric, query, row);
+                    assert!((dense - scalar).abs() < 1e-9, "{:?}: {} != {}", metric, dense, scalar);
+                }
+                let dense: Vec<f64> = index.nearest(query, 7).iter().map(|n| n.distance).collect();
+                let scalar: Vec<f64> = get_neighbors(&train, query, 7, metric).iter().map(|n| n.distance).collect();
+                assert_eq!(dense.len(), scalar.len());
+                for (d, s) in dense.iter().zip(&scalar) {
+                    assert!((d - s).abs() < 1e-9, "{:?}: {:?} != {:?}", metric, dense, scalar);
+                }
+            }
+        }
+    }
+}
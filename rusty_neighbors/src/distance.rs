@@ -0,0 +1,463 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::data::{Feature, Sample};
+
+/// Which distance function to use when comparing two samples.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DistanceMetric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    Minkowski(f64),
+    Cosine,
+    Canberra,
+    BrayCurtis,
+    Correlation,
+    Hamming,
+}
+
+impl FromStr for DistanceMetric {
+    type Err = String;
+
+    /// Parses `euclidean`, `manhattan`, `chebyshev`, `cosine`, `canberra`,
+    /// `braycurtis`, `correlation`, `hamming` or `minkowski:<p>`.
+    fn from_str(s: &str) -> Result<DistanceMetric, String> {
+        match s.to_lowercase().as_str() {
+            "euclidean" => Ok(DistanceMetric::Euclidean),
+            "manhattan" => Ok(DistanceMetric::Manhattan),
+            "chebyshev" => Ok(DistanceMetric::Chebyshev),
+            "cosine" => Ok(DistanceMetric::Cosine),
+            "canberra" => Ok(DistanceMetric::Canberra),
+            "braycurtis" => Ok(DistanceMetric::BrayCurtis),
+            "correlation" => Ok(DistanceMetric::Correlation),
+            "hamming" => Ok(DistanceMetric::Hamming),
+            other => match other.strip_prefix("minkowski:") {
+                Some(p) => match p.parse::<f64>() {
+                    Ok(p) if p > 0.0 => Ok(DistanceMetric::Minkowski(p)),
+                    _ => Err(format!("invalid minkowski exponent {:?}", p)),
+                },
+                None => Err(format!("unknown distance metric {:?}", s)),
+            },
+        }
+    }
+}
+
+/// The overlapping features of two rows, pairwise, widened to `f64`.
+fn feature_pairs<'a, L, F: Feature>(row1: &'a Sample<L, F>, row2: &'a Sample<L, F>) -> impl Iterator<Item = (f64, f64)> + 'a {
+    row1.rowify().iter().zip(row2.rowify()).map(|(x, y)| (x.to_f64(), y.to_f64()))
+}
+
+pub fn distance<L, F: Feature>(metric: DistanceMetric, a: &Sample<L, F>, b: &Sample<L, F>) -> f64 {
+    match metric {
+        DistanceMetric::Euclidean => euclidean_distance(a, b),
+        DistanceMetric::Manhattan => manhattan_distance(a, b),
+        DistanceMetric::Chebyshev => chebyshev_distance(a, b),
+        DistanceMetric::Minkowski(p) => minkowski_distance(a, b, p),
+        DistanceMetric::Cosine => cosine_distance(a, b),
+        DistanceMetric::Canberra => canberra_distance(a, b),
+        DistanceMetric::BrayCurtis => bray_curtis_distance(a, b),
+        DistanceMetric::Correlation => correlation_distance(a, b),
+        DistanceMetric::Hamming => hamming_distance(a, b),
+    }
+}
+
+/// Straight-line distance between two rows. If the rows have different
+/// lengths only the overlapping features are compared; this holds for every
+/// distance function below.
+pub fn euclidean_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    squared_euclidean_distance(row1, row2).sqrt()
+}
+
+/// Euclidean distance without the final `sqrt`. It ranks rows in the same
+/// order as `euclidean_distance`, so it is enough when only ordering matters.
+pub fn squared_euclidean_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    feature_pairs(row1, row2)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum()
+}
+
+/// Euclidean distance over just the columns in `features`, e.g. to see how a
+/// model does without some of them. Indices either row lacks are skipped, as
+/// with the overlap rule elsewhere.
+pub fn euclidean_distance_subset<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, features: &[usize]) -> f64 {
+    features.iter()
+        .filter_map(|&i| Some((row1.features.get(i)?.to_f64(), row2.features.get(i)?.to_f64())))
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+pub fn manhattan_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    feature_pairs(row1, row2)
+        .map(|(x, y)| (x - y).abs())
+        .sum()
+}
+
+pub fn chebyshev_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    feature_pairs(row1, row2)
+        .map(|(x, y)| (x - y).abs())
+        .fold(0.0, f64::max)
+}
+
+/// Generalized distance `(sum |xi - yi|^p)^(1/p)`. `p = 1.0` is Manhattan and
+/// `p = 2.0` is Euclidean; `p` is expected to be at least 1.0.
+///
+/// Panics if `p <= 0.0`.
+pub fn minkowski_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, p: f64) -> f64 {
+    assert!(p > 0.0, "minkowski_distance requires p > 0, got {}", p);
+    feature_pairs(row1, row2)
+        .map(|(x, y)| (x - y).abs().powf(p))
+        .sum::<f64>()
+        .powf(1.0 / p)
+}
+
+/// Euclidean distance with each squared difference scaled by a per-feature
+/// weight, so noisy columns can be down-weighted (or ignored with 0.0).
+///
+/// Panics if `weights` doesn't have one entry per feature of `row1`.
+pub fn weighted_euclidean_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, weights: &[f64]) -> f64 {
+    assert_eq!(weights.len(), row1.features.len(), "expected one weight per feature");
+    feature_pairs(row1, row2)
+        .zip(weights)
+        .map(|((x, y), w)| w * (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Minkowski distance with each `|x - y|^p` term scaled by a per-feature
+/// weight. Uniform weights of 1.0 give `minkowski_distance`; p = 2 is
+/// `weighted_euclidean_distance` and p = 1 a weighted Manhattan distance.
+///
+/// Panics if `weights` doesn't have one entry per feature of `row1`, or if
+/// `p < 1`.
+pub fn weighted_minkowski<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, weights: &[f64], p: f64) -> f64 {
+    assert_eq!(weights.len(), row1.features.len(), "expected one weight per feature");
+    assert!(p >= 1.0, "weighted_minkowski requires p >= 1, got {}", p);
+    feature_pairs(row1, row2)
+        .zip(weights)
+        .map(|((x, y), w)| w * (x - y).abs().powf(p))
+        .sum::<f64>()
+        .powf(1.0 / p)
+}
+
+/// `1 - cos(angle)` between the two feature vectors: 0.0 for parallel rows,
+/// 1.0 for orthogonal ones and 2.0 for opposite ones. The angle is undefined
+/// when either row is all zeros; that case returns 1.0, as if orthogonal.
+pub fn cosine_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    let mut dot = 0.0;
+    let mut norm1 = 0.0;
+    let mut norm2 = 0.0;
+    for (x, y) in feature_pairs(row1, row2) {
+        dot += x * y;
+        norm1 += x * x;
+        norm2 += y * y;
+    }
+    if norm1 == 0.0 || norm2 == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm1.sqrt() * norm2.sqrt())
+}
+
+/// `sum |xi - yi| / (|xi| + |yi|)`. Each term is at most 1.0, so features
+/// near zero weigh as much as large ones. Terms where both values are zero
+/// contribute 0.0.
+pub fn canberra_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    feature_pairs(row1, row2)
+        .map(|(x, y)| {
+            let denominator = x.abs() + y.abs();
+            if denominator == 0.0 { 0.0 } else { (x - y).abs() / denominator }
+        })
+        .sum()
+}
+
+/// `sum |xi - yi| / sum |xi + yi|`, in 0..1 for non-negative features such
+/// as counts or proportions. Returns 0.0 when the denominator is zero.
+pub fn bray_curtis_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    let mut difference = 0.0;
+    let mut total = 0.0;
+    for (x, y) in feature_pairs(row1, row2) {
+        difference += (x - y).abs();
+        total += (x + y).abs();
+    }
+    if total == 0.0 { 0.0 } else { difference / total }
+}
+
+/// `1 - pearson(row1, row2)`: 0.0 for rows with the same shape whatever
+/// their offset and scale, 2.0 for mirror-image rows. Pearson correlation is
+/// undefined when either row is constant; that case returns 1.0, as if
+/// uncorrelated.
+pub fn correlation_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    let n = feature_pairs(row1, row2).count() as f64;
+    let (sum1, sum2) = feature_pairs(row1, row2).fold((0.0, 0.0), |(a, b), (x, y)| (a + x, b + y));
+    let (mean1, mean2) = (sum1 / n, sum2 / n);
+
+    let mut covariance = 0.0;
+    let mut variance1 = 0.0;
+    let mut variance2 = 0.0;
+    for (x, y) in feature_pairs(row1, row2) {
+        covariance += (x - mean1) * (y - mean2);
+        variance1 += (x - mean1).powi(2);
+        variance2 += (y - mean2).powi(2);
+    }
+    if variance1 == 0.0 || variance2 == 0.0 {
+        return 1.0;
+    }
+    1.0 - covariance / (variance1.sqrt() * variance2.sqrt())
+}
+
+/// The number of features that differ, for categorical or binary codes
+/// where the size of a difference means nothing.
+pub fn hamming_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>) -> f64 {
+    feature_pairs(row1, row2)
+        .filter(|(x, y)| x != y)
+        .count() as f64
+}
+
+/// How `gower_distance` compares a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeatureKind {
+    /// A measurement: differences are divided by the column's range.
+    Numeric,
+    /// A category code: values either match (0) or don't (1).
+    Categorical,
+}
+
+/// Gower distance for rows mixing measurements and category codes: the mean
+/// over features of a per-feature dissimilarity in 0..1. Numeric columns
+/// contribute `|x - y| / range`, using the column ranges (max - min) of the
+/// training set, e.g. from `dataset_minmax`; a zero range contributes 0.
+/// Categorical columns contribute 0 on a match and 1 otherwise. The result is
+/// in 0..1 as long as the rows lie within the ranges. Use it through
+/// `get_neighbors_with`.
+///
+/// Panics if `kinds` or `ranges` doesn't have one entry per feature of
+/// `row1`.
+pub fn gower_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, kinds: &[FeatureKind], ranges: &[f64]) -> f64 {
+    assert_eq!(kinds.len(), row1.features.len(), "expected one feature kind per feature");
+    assert_eq!(ranges.len(), row1.features.len(), "expected one range per feature");
+    let mut total = 0.0;
+    let mut n = 0;
+    for (((x, y), kind), &range) in feature_pairs(row1, row2).zip(kinds).zip(ranges) {
+        total += match kind {
+            FeatureKind::Numeric if range == 0.0 => 0.0,
+            FeatureKind::Numeric => (x - y).abs() / range,
+            FeatureKind::Categorical => if x == y { 0.0 } else { 1.0 },
+        };
+        n += 1;
+    }
+    total / n as f64
+}
+
+/// `sqrt((x - y)^T inv_cov (x - y))`: Euclidean distance after undoing the
+/// correlations and scales captured by a covariance matrix. `inv_cov` is the
+/// inverse covariance, e.g. from `inverse_covariance`.
+///
+/// Panics if `inv_cov` isn't square with one row per feature of `row1`.
+pub fn mahalanobis_distance<L, F: Feature>(row1: &Sample<L, F>, row2: &Sample<L, F>, inv_cov: &[Vec<f64>]) -> f64 {
+    assert_eq!(inv_cov.len(), row1.features.len(), "expected one inverse covariance row per feature");
+    assert!(inv_cov.iter().all(|row| row.len() == inv_cov.len()), "inverse covariance must be square");
+    let diff: Vec<f64> = feature_pairs(row1, row2).map(|(x, y)| x - y).collect();
+    let mut sum = 0.0;
+    for (i, di) in diff.iter().enumerate() {
+        for (j, dj) in diff.iter().enumerate() {
+            sum += di * inv_cov[i][j] * dj;
+        }
+    }
+    // Rounding can push a near-zero quadratic form slightly negative.
+    sum.max(0.0).sqrt()
+}
+
+/// Population covariance matrix of the features, one row and column per
+/// feature.
+pub fn covariance_matrix<L, F: Feature>(dataset: &[Sample<L, F>]) -> Vec<Vec<f64>> {
+    let n_features = dataset.first().map_or(0, |row| row.features.len());
+    let n = dataset.len() as f64;
+    let mut means = vec![0.0; n_features];
+    for row in dataset {
+        for (mean, value) in means.iter_mut().zip(&row.features) {
+            *mean += value.to_f64() / n;
+        }
+    }
+
+    let mut covariance = vec![vec![0.0; n_features]; n_features];
+    for row in dataset {
+        for i in 0..n_features {
+            for j in 0..n_features {
+                covariance[i][j] += (row.features[i].to_f64() - means[i]) * (row.features[j].to_f64() - means[j]) / n;
+            }
+        }
+    }
+    covariance
+}
+
+/// Inverts a square matrix by Gauss-Jordan elimination with partial
+/// pivoting. Returns `None` if it is singular.
+pub fn invert_matrix(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    // Reduce [matrix | identity] until the left half is the identity.
+    let mut augmented: Vec<Vec<f64>> = matrix.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut row = row.clone();
+            row.extend((0..n).map(|j| if i == j { 1.0 } else { 0.0 }));
+            row
+        })
+        .collect();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| augmented[a][col].abs().total_cmp(&augmented[b][col].abs()))?;
+        if augmented[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        augmented.swap(col, pivot);
+
+        let scale = augmented[col][col];
+        for value in augmented[col].iter_mut() {
+            *value /= scale;
+        }
+        let pivot_row = augmented[col].clone();
+        for (i, row) in augmented.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col];
+                for (value, pivot_value) in row.iter_mut().zip(&pivot_row) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some(augmented.into_iter().map(|row| row[n..].to_vec()).collect())
+}
+
+/// The inverse of `covariance_matrix(dataset)`, ready for
+/// `mahalanobis_distance`. `None` when features are linearly dependent, e.g.
+/// a constant column or fewer rows than features.
+pub fn inverse_covariance<L, F: Feature>(dataset: &[Sample<L, F>]) -> Option<Vec<Vec<f64>>> {
+    invert_matrix(&covariance_matrix(dataset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(features: &[f64]) -> Sample {
+        Sample::new(features.to_vec(), "x")
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn manhattan_sums_absolute_differences() {
+        // |1 - 4| + |-2 - 2| + |3.5 - 0.5| = 3 + 4 + 3
+        assert_eq!(manhattan_distance(&sample(&[1.0, -2.0, 3.5]), &sample(&[4.0, 2.0, 0.5])), 10.0);
+        assert_eq!(manhattan_distance(&sample(&[1.0, 1.0]), &sample(&[1.0, 1.0])), 0.0);
+    }
+
+    #[test]
+    fn minkowski_with_p_2_is_euclidean() {
+        let a = sample(&[5.1, 3.5, 1.4, 0.2]);
+        let b = sample(&[6.3, 3.3, 6.0, 2.5]);
+        assert_close(minkowski_distance(&a, &b, 2.0), euclidean_distance(&a, &b));
+        assert_close(minkowski_distance(&a, &b, 1.0), manhattan_distance(&a, &b));
+    }
+
+    #[test]
+    fn weighted_minkowski_with_uniform_weights_and_p_2_is_euclidean() {
+        let a = sample(&[1.0, -2.0, 3.5]);
+        let b = sample(&[4.0, 2.0, 0.5]);
+        assert_close(weighted_minkowski(&a, &b, &[1.0, 1.0, 1.0], 2.0), euclidean_distance(&a, &b));
+    }
+
+    #[test]
+    fn rows_of_different_lengths_compare_their_overlap() {
+        let short = sample(&[0.0, 0.0]);
+        let long = sample(&[3.0, 4.0, 100.0]);
+        assert_eq!(euclidean_distance(&short, &long), 5.0);
+        assert_eq!(euclidean_distance(&long, &short), 5.0);
+    }
+
+    #[test]
+    fn a_zero_weight_ignores_its_feature() {
+        let query = sample(&[0.0, 0.0]);
+        let near = sample(&[3.0, 1.0]);
+        let far = sample(&[3.0, 50.0]);
+        let weights = [1.0, 0.0];
+        assert_eq!(weighted_euclidean_distance(&query, &near, &weights), weighted_euclidean_distance(&query, &far, &weights));
+        assert_eq!(weighted_euclidean_distance(&query, &near, &weights), 3.0);
+        assert_close(weighted_euclidean_distance(&query, &near, &[1.0, 1.0]), euclidean_distance(&query, &near));
+    }
+
+    #[test]
+    fn cosine_distance_of_parallel_orthogonal_and_zero_vectors() {
+        assert_close(cosine_distance(&sample(&[1.0, 2.0]), &sample(&[3.0, 6.0])), 0.0);
+        assert_close(cosine_distance(&sample(&[1.0, 0.0]), &sample(&[0.0, 5.0])), 1.0);
+        assert_close(cosine_distance(&sample(&[1.0, 1.0]), &sample(&[-2.0, -2.0])), 2.0);
+        // The zero vector has no direction and counts as orthogonal.
+        assert_eq!(cosine_distance(&sample(&[0.0, 0.0]), &sample(&[1.0, 2.0])), 1.0);
+    }
+
+    #[test]
+    fn canberra_and_bray_curtis_match_hand_computed_values() {
+        // 2/4 + 0 (a 0/0 term) + 4/4
+        assert_close(canberra_distance(&sample(&[1.0, 0.0, 3.0]), &sample(&[3.0, 0.0, -1.0])), 1.5);
+        assert_eq!(canberra_distance(&sample(&[0.0, 0.0]), &sample(&[0.0, 0.0])), 0.0);
+
+        // (2 + 2) / (4 + 6)
+        assert_close(bray_curtis_distance(&sample(&[1.0, 2.0]), &sample(&[3.0, 4.0])), 0.4);
+        assert_eq!(bray_curtis_distance(&sample(&[0.0, 0.0]), &sample(&[0.0, 0.0])), 0.0);
+    }
+
+    #[test]
+    fn correlation_distance_of_correlated_and_anti_correlated_rows() {
+        let row = sample(&[1.0, 2.0, 3.0, 5.0]);
+        assert_close(correlation_distance(&row, &sample(&[3.0, 5.0, 7.0, 11.0])), 0.0);
+        assert_close(correlation_distance(&row, &sample(&[-1.0, -2.0, -3.0, -5.0])), 2.0);
+        // A constant row has no correlation to speak of.
+        assert_eq!(correlation_distance(&row, &sample(&[4.0, 4.0, 4.0, 4.0])), 1.0);
+    }
+
+    #[test]
+    fn euclidean_distance_subset_of_one_feature_is_the_1d_distance() {
+        let a = sample(&[1.0, -2.0, 3.5]);
+        let b = sample(&[4.0, 2.0, 0.5]);
+        for i in 0..3 {
+            let one_d = euclidean_distance(&sample(&[a.features[i]]), &sample(&[b.features[i]]));
+            assert_close(euclidean_distance_subset(&a, &b, &[i]), one_d);
+        }
+        assert_eq!(euclidean_distance_subset(&a, &b, &[1]), 4.0);
+        assert_close(euclidean_distance_subset(&a, &b, &[0, 1, 2]), euclidean_distance(&a, &b));
+    }
+
+    #[test]
+    fn hamming_counts_the_positions_that_differ() {
+        let a = sample(&[1.0, 0.0, 2.0, 3.0, 0.0]);
+        let b = sample(&[1.0, 1.0, 2.0, 4.0, 0.0]);
+        assert_eq!(hamming_distance(&a, &b), 2.0);
+        assert_eq!(distance(DistanceMetric::Hamming, &a, &b), 2.0);
+        assert_eq!(hamming_distance(&a, &a), 0.0);
+    }
+
+    #[test]
+    fn gower_mixes_numeric_and_categorical_columns_within_zero_to_one() {
+        // Height, a color code and weight.
+        let rows = [
+            sample(&[150.0, 1.0, 60.0]),
+            sample(&[155.0, 2.0, 80.0]),
+            sample(&[160.0, 1.0, 30.0]),
+            sample(&[152.0, 3.0, 70.0]),
+        ];
+        let kinds = [FeatureKind::Numeric, FeatureKind::Categorical, FeatureKind::Numeric];
+        let ranges = [10.0, 0.0, 50.0];
+
+        // (5 / 10 + 1 + 20 / 50) / 3
+        assert_close(gower_distance(&rows[0], &rows[1], &kinds, &ranges), 1.9 / 3.0);
+        for a in &rows {
+            assert_eq!(gower_distance(a, a, &kinds, &ranges), 0.0);
+            for b in &rows {
+                let d = gower_distance(a, b, &kinds, &ranges);
+                assert!((0.0..=1.0).contains(&d), "{}", d);
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why loading data, searching for neighbors, predicting or evaluating could
+/// not be carried out.
+#[derive(Debug)]
+pub enum KnnError {
+    /// Reading a file failed.
+    Io(io::Error),
+    /// The CSV itself was malformed, e.g. rows of different lengths.
+    Csv(csv::Error),
+    /// A CSV record was well-formed but its values weren't usable.
+    Parse { line: u64, message: String },
+    /// The dataset to evaluate has no rows.
+    EmptyDataset,
+    /// The training set has no rows.
+    EmptyTraining,
+    /// `num_neighbors` was 0.
+    InvalidK(usize),
+    /// More neighbors were asked for than there are training rows.
+    KTooLarge { k: usize, n_rows: usize },
+    /// Cross-validation needs at least two folds.
+    InvalidFolds(usize),
+    /// Repeated cross-validation needs at least one repeat.
+    InvalidRepeats(usize),
+    /// A row has a different number of features than the training rows.
+    DimensionMismatch { expected: usize, found: usize },
+    /// A `DistanceMetric::Minkowski` exponent that isn't positive.
+    InvalidMinkowskiP(f64),
+    /// A `Voting::Kernel` bandwidth that isn't positive.
+    InvalidBandwidth(f64),
+    /// A distance among the selected neighbors came out NaN, e.g. from a NaN
+    /// feature.
+    NanDistance,
+    /// The training features are linearly dependent, e.g. a constant column,
+    /// so their covariance matrix has no inverse for Mahalanobis distance.
+    SingularCovariance,
+}
+
+impl fmt::Display for KnnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KnnError::Io(e) => write!(f, "{}", e),
+            KnnError::Csv(e) => write!(f, "{}", e),
+            KnnError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            KnnError::EmptyDataset => write!(f, "the dataset is empty"),
+            KnnError::EmptyTraining => write!(f, "the training set is empty"),
+            KnnError::InvalidK(k) => write!(f, "num_neighbors must be at least 1, got {}", k),
+            KnnError::KTooLarge { k, n_rows } => write!(f, "asked for {} neighbors but there are only {} training rows", k, n_rows),
+            KnnError::InvalidFolds(n) => write!(f, "cross-validation needs at least 2 folds, got {}", n),
+            KnnError::InvalidRepeats(n) => write!(f, "repeated cross-validation needs at least 1 repeat, got {}", n),
+            KnnError::DimensionMismatch { expected, found } => write!(f, "expected {} features, found {}", expected, found),
+            KnnError::InvalidMinkowskiP(p) => write!(f, "the minkowski exponent must be positive, got {}", p),
+            KnnError::InvalidBandwidth(bandwidth) => write!(f, "the kernel bandwidth must be positive, got {}", bandwidth),
+            KnnError::NanDistance => write!(f, "a neighbor distance is NaN"),
+            KnnError::SingularCovariance => write!(f, "the training features are linearly dependent, so their covariance has no inverse"),
+        }
+    }
+}
+
+impl Error for KnnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            KnnError::Io(e) => Some(e),
+            KnnError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KnnError {
+    fn from(e: io::Error) -> KnnError {
+        KnnError::Io(e)
+    }
+}
+
+impl From<csv::Error> for KnnError {
+    fn from(e: csv::Error) -> KnnError {
+        KnnError::Csv(e)
+    }
+}
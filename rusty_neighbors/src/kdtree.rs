@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use crate::data::Sample;
+use crate::distance::{distance, DistanceMetric};
+use crate::knn::{compare_distances, MeasuredFlower};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    /// Index into `KdTree::points`.
+    point: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// A k-d tree over a training set, answering k-nearest queries without
+/// scanning every row. Each level splits on one feature at the median, and
+/// a subtree is skipped when the splitting plane alone is farther away than
+/// the current k-th nearest neighbor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdTree<L = String> {
+    points: Vec<Sample<L>>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+    metric: DistanceMetric,
+}
+
+impl KdTree {
+    /// Whether `metric` can be searched with a k-d tree. Pruning needs the
+    /// distance along one axis to be a lower bound on the full distance.
+    pub fn supports(metric: DistanceMetric) -> bool {
+        match metric {
+            DistanceMetric::Euclidean
+            | DistanceMetric::Manhattan
+            | DistanceMetric::Chebyshev
+            | DistanceMetric::Minkowski(_) => true,
+            DistanceMetric::Cosine
+            | DistanceMetric::Canberra
+            | DistanceMetric::BrayCurtis
+            | DistanceMetric::Correlation
+            | DistanceMetric::Hamming => false,
+        }
+    }
+}
+
+impl<L: Clone> KdTree<L> {
+    /// Builds a tree over `points`.
+    ///
+    /// Panics if `KdTree::supports(metric)` is false.
+    pub fn new(points: Vec<Sample<L>>, metric: DistanceMetric) -> KdTree<L> {
+        assert!(KdTree::supports(metric), "{:?} cannot be searched with a k-d tree", metric);
+        let dims = points.first().map_or(0, |p| p.features.len());
+        let mut tree = KdTree {
+            points,
+            nodes: Vec::new(),
+            root: None,
+            metric,
+        };
+        let mut indices: Vec<usize> = (0..tree.points.len()).collect();
+        tree.root = tree.build(&mut indices, 0, dims);
+        tree
+    }
+
+    fn build(&mut self, indices: &mut [usize], depth: usize, dims: usize) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+        let axis = if dims == 0 { 0 } else { depth % dims };
+        let mid = indices.len() / 2;
+        let points = &self.points;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            compare_distances(points[a].features[axis], points[b].features[axis])
+        });
+
+        let node = self.nodes.len();
+        self.nodes.push(Node { point: indices[mid], axis, left: None, right: None });
+        let (left, rest) = indices.split_at_mut(mid);
+        self.nodes[node].left = self.build(left, depth + 1, dims);
+        self.nodes[node].right = self.build(&mut rest[1..], depth + 1, dims);
+        Some(node)
+    }
+
+    /// The rows the tree was built from, in their original order.
+    pub fn points(&self) -> &[Sample<L>] {
+        &self.points
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// The `k` points closest to `query`, nearest first, matching what
+    /// `get_neighbors` returns for the same rows.
+    pub fn nearest(&self, query: &Sample<L>, k: usize) -> Vec<MeasuredFlower<L>> {
+        // (distance, point index), kept sorted and at most `k` long
+        let mut best = Vec::new();
+        if k > 0 {
+            self.search(self.root, query, k, &mut best);
+        }
+        best.into_iter()
+            .map(|(distance, i)| MeasuredFlower { distance, class: self.points[i].class.clone() })
+            .collect()
+    }
+
+    fn search(&self, node: Option<usize>, query: &Sample<L>, k: usize, best: &mut Vec<(f64, usize)>) {
+        let Some(node) = node else {
+            return;
+        };
+        let node = &self.nodes[node];
+        let point = &self.points[node.point];
+
+        let dist = distance(self.metric, query, point);
+        let pos = best.partition_point(|&(d, _)| compare_distances(d, dist).is_le());
+        if pos < k {
+            best.insert(pos, (dist, node.point));
+            best.truncate(k);
+        }
+
+        // A query missing this axis can't be pruned on it, so search both sides.
+        let Some(&q) = query.features.get(node.axis) else {
+            self.search(node.left, query, k, best);
+            self.search(node.right, query, k, best);
+            return;
+        };
+        let diff = q - point.features[node.axis];
+        let (near, far) = if diff <= 0.0 { (node.left, node.right) } else { (node.right, node.left) };
+
+        self.search(near, query, k, best);
+        let worst = best.last().map_or(f64::INFINITY, |&(d, _)| d);
+        if best.len() < k || worst.is_nan() || diff.abs() <= worst {
+            self.search(far, query, k, best);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+    use crate::knn::get_neighbors;
+
+    /// Random rows labelled with their index.
+    fn random_rows(rng: &mut StdRng, n_rows: usize, n_features: usize) -> Vec<Sample<usize>> {
+        (0..n_rows)
+            .map(|i| Sample { features: (0..n_features).map(|_| rng.gen_range(-10.0..10.0)).collect(), class: i })
+            .collect()
+    }
+
+    fn summary(neighbors: &[MeasuredFlower<usize>]) -> Vec<(usize, f64)> {
+        neighbors.iter().map(|n| (n.class, n.distance)).collect()
+    }
+
+    #[test]
+    fn kd_tree_nearest_matches_get_neighbors_on_random_data() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let train = random_rows(&mut rng, 300, 3);
+        let queries = random_rows(&mut rng, 20, 3);
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Chebyshev, DistanceMetric::Minkowski(3.0)] {
+            let tree = KdTree::new(train.clone(), metric);
+            for query in &queries {
+                for k in [1, 5, 17] {
+                    let expected = get_neighbors(&train, query, k, metric);
+                    assert_eq!(summary(&tree.nearest(query, k)), summary(&expected), "{:?}, k = {}", metric, k);
+                }
+            }
+        }
+    }
+}
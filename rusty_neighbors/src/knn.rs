@@ -0,0 +1,1281 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use serde::{Deserialize, Serialize};
+use crate::data::{Feature, RegressionSample, Sample};
+use crate::distance::{distance, inverse_covariance, mahalanobis_distance, squared_euclidean_distance, DistanceMetric};
+use crate::error::KnnError;
+use crate::balltree::BallTree;
+use crate::kdtree::KdTree;
+#[cfg(feature = "ndarray")]
+use crate::dense::DenseIndex;
+use crate::preprocessing::Scaler;
+use crate::topk::{ByDistance, TopK};
+
+/// A training row's class paired with its distance from a query row.
+///
+/// Neighbors order by distance, then by class. Distances compare as
+/// `compare_distances` does, so a NaN distance equals itself and sorts after
+/// every real distance, deterministically. With an `Ord` class that makes
+/// `MeasuredFlower` usable directly with `sort` and `BinaryHeap`.
+#[derive(Debug, Clone)]
+pub struct MeasuredFlower<L = String> {
+    pub distance: f64,
+    pub class: L,
+}
+
+impl<L: PartialEq> PartialEq for MeasuredFlower<L> {
+    fn eq(&self, other: &Self) -> bool {
+        compare_distances(self.distance, other.distance).is_eq() && self.class == other.class
+    }
+}
+
+impl<L: Eq> Eq for MeasuredFlower<L> {}
+
+impl<L: PartialOrd> PartialOrd for MeasuredFlower<L> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match compare_distances(self.distance, other.distance) {
+            Ordering::Equal => self.class.partial_cmp(&other.class),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl<L: Ord> Ord for MeasuredFlower<L> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_distances(self.distance, other.distance).then_with(|| self.class.cmp(&other.class))
+    }
+}
+
+/// How neighbors are combined into a prediction.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Voting {
+    /// Every neighbor gets one vote.
+    Majority,
+    /// Every neighbor votes with weight `1 / (distance + VOTE_EPSILON)`.
+    InverseDistance,
+    /// Gaussian kernel: every neighbor votes with weight
+    /// `exp(-distance^2 / (2 * bandwidth^2))`. A large bandwidth approaches
+    /// majority voting. A small one approaches 1-nearest-neighbor; once every
+    /// weight underflows to zero the tie-break picks the nearest class, but
+    /// vote shares and regression means become NaN.
+    Kernel { bandwidth: f64 },
+    /// Every neighbor votes with weight `1 / n`, where `n` is the number of
+    /// training rows in its class, so rare classes aren't drowned out. This
+    /// needs the training set's class frequencies: the `predict_*` functions
+    /// and `KnnClassifier` supply them, and bare neighbor sets go through
+    /// `vote_with_frequencies`. Regression treats it like `Majority`.
+    ClassBalanced,
+}
+
+/// Keeps inverse-distance weights finite when a neighbor is an exact match.
+pub const VOTE_EPSILON: f64 = 1e-9;
+
+impl Voting {
+    /// The distance-dependent part of a neighbor's vote. `ClassBalanced`'s
+    /// class weighting is applied on top when votes are tallied.
+    ///
+    /// Panics for `Voting::Kernel` with a bandwidth that isn't positive.
+    pub fn weight(&self, distance: f64) -> f64 {
+        match *self {
+            Voting::Majority | Voting::ClassBalanced => 1.0,
+            Voting::InverseDistance => 1.0 / (distance + VOTE_EPSILON),
+            Voting::Kernel { bandwidth } => {
+                assert!(bandwidth > 0.0, "kernel bandwidth must be positive, got {}", bandwidth);
+                (-distance.powi(2) / (2.0 * bandwidth.powi(2))).exp()
+            }
+        }
+    }
+}
+
+/// Vote over the nearest neighbors, weighted according to `voting`. Ties on
+/// total weight go to the class whose closest member is nearest to
+/// `test_row`, then to the lexicographically smallest class name, so the
+/// result is deterministic.
+///
+/// Fails when there is nothing to vote with (`train` is empty or
+/// `num_neighbors` is 0), when `test_row` has a different number of features
+/// than the first training row, when `voting` is a `Voting::Kernel` without a
+/// positive bandwidth, or when `try_get_neighbors` does.
+pub fn predict_classification(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<String, KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    check_voting(voting)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    Ok(vote_with_frequencies(neighbors, voting, &frequencies_for(train, voting)).expect(NO_NEIGHBORS))
+}
+
+/// `predict_classification` with a custom distance function, as in
+/// `get_neighbors_with`.
+pub fn predict_classification_with<F>(train: &[Sample], test_row: &Sample, num_neighbors: usize, distance_fn: F, voting: Voting) -> Result<String, KnnError>
+where
+    F: Fn(&Sample, &Sample) -> f64,
+{
+    check_prediction(train, test_row, num_neighbors)?;
+    check_voting(voting)?;
+    check_search(train.len(), num_neighbors)?;
+    let neighbors = check_distances(get_neighbors_with(train, test_row, num_neighbors, distance_fn))?;
+    Ok(vote_with_frequencies(neighbors, voting, &frequencies_for(train, voting)).expect(NO_NEIGHBORS))
+}
+
+/// The checks on a prediction's inputs behind `predict_classification`'s
+/// errors; the search itself is checked by `try_get_neighbors`.
+fn check_prediction<L, F>(train: &[Sample<L, F>], test_row: &Sample<L, F>, num_neighbors: usize) -> Result<(), KnnError> {
+    let Some(first) = train.first() else {
+        return Err(KnnError::EmptyTraining);
+    };
+    if num_neighbors == 0 {
+        return Err(KnnError::InvalidK(num_neighbors));
+    }
+    if test_row.features.len() != first.features.len() {
+        return Err(KnnError::DimensionMismatch { expected: first.features.len(), found: test_row.features.len() });
+    }
+    Ok(())
+}
+
+/// Settings for `predict_adaptive`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveK {
+    pub metric: DistanceMetric,
+    pub voting: Voting,
+    /// Neighbors to vote with per unit of distance from the query to its
+    /// nearest neighbor, rounded up.
+    pub distance_scale: f64,
+    /// Used however close the nearest neighbor is.
+    pub min_neighbors: usize,
+    /// Never exceeded, however far the nearest neighbor is. A value below
+    /// `min_neighbors` counts as `min_neighbors`.
+    pub max_neighbors: usize,
+}
+
+/// Like `predict_classification`, but k adapts to the local density: it is
+/// the distance from `test_row` to its nearest neighbor times
+/// `params.distance_scale`, rounded up and kept within
+/// `min_neighbors..=max_neighbors`. Queries in dense regions, whose nearest
+/// neighbor is close, vote with few neighbors and keep the local detail;
+/// isolated queries widen the vote to smooth over their sparse
+/// surroundings. Returns the prediction and the number of neighbors it used.
+///
+/// Fails as `predict_classification` does, with `min_neighbors` in place of
+/// `num_neighbors`.
+pub fn predict_adaptive(train: &[Sample], test_row: &Sample, params: &AdaptiveK) -> Result<(String, usize), KnnError> {
+    check_prediction(train, test_row, params.min_neighbors)?;
+    check_voting(params.voting)?;
+    check_metric(params.metric)?;
+    check_search(train.len(), params.min_neighbors)?;
+    let max_neighbors = params.max_neighbors.max(params.min_neighbors);
+    let mut neighbors = get_neighbors(train, test_row, max_neighbors, params.metric);
+    let scaled = (neighbors[0].distance * params.distance_scale).ceil() as usize;
+    neighbors.truncate(scaled.clamp(params.min_neighbors, max_neighbors));
+    let neighbors = check_distances(neighbors)?;
+    let used = neighbors.len();
+    let class = vote_with_frequencies(neighbors, params.voting, &frequencies_for(train, params.voting)).expect(NO_NEIGHBORS);
+    Ok((class, used))
+}
+
+/// Majority-vote prediction plus its confidence: the fraction of the
+/// neighbors that belong to the winning class, e.g. 4 of 5 gives 0.8.
+/// Fails as `predict_classification` does.
+pub fn predict_with_confidence(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric) -> Result<(String, f64), KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    let total = neighbors.len();
+    let winner = vote(neighbors.clone(), Voting::Majority).expect(NO_NEIGHBORS);
+    let agreeing = neighbors.iter().filter(|n| n.class == winner).count();
+    Ok((winner, agreeing as f64 / total as f64))
+}
+
+/// Like `predict_with_confidence`, but abstains with `None` unless the
+/// confidence is strictly greater than `threshold`. A confidence exactly at
+/// the threshold, such as a 2-2 split against 0.5, abstains.
+pub fn predict_or_abstain(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric, threshold: f64) -> Result<Option<String>, KnnError> {
+    let (class, confidence) = predict_with_confidence(train, test_row, num_neighbors, metric)?;
+    if confidence > threshold {
+        Ok(Some(class))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Majority-vote prediction together with the training rows that drove it:
+/// the `num_neighbors` nearest rows and their distances, nearest first.
+/// Fails as `predict_classification` does.
+pub fn explain_prediction(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric) -> Result<(String, Vec<(Sample, f64)>), KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    check_metric(metric)?;
+    check_search(train.len(), num_neighbors)?;
+    let mut ranked: Vec<(usize, f64)> = train.iter()
+        .enumerate()
+        .map(|(i, train_row)| (i, distance(metric, test_row, train_row)))
+        .collect();
+    ranked.sort_by(|a, b| compare_distances(a.1, b.1));
+    ranked.truncate(num_neighbors);
+
+    let neighbors = ranked.iter()
+        .map(|&(i, distance)| MeasuredFlower { distance, class: train[i].class.clone() })
+        .collect();
+    let class = vote(check_distances(neighbors)?, Voting::Majority).expect(NO_NEIGHBORS);
+    let rows = ranked.into_iter()
+        .map(|(i, distance)| (train[i].clone(), distance))
+        .collect();
+    Ok((class, rows))
+}
+
+/// The class `predict_classification` picks from an already-found set of
+/// neighbors, or `None` if the set is empty. Without the training set's
+/// class frequencies `Voting::ClassBalanced` counts like `Voting::Majority`;
+/// use `vote_with_frequencies` to supply them.
+pub fn vote(neighbors: Vec<MeasuredFlower>, voting: Voting) -> Option<String> {
+    vote_with_frequencies(neighbors, voting, &HashMap::new())
+}
+
+/// Like `vote`, with `frequencies` (see `class_frequencies`) giving the
+/// training-set class counts that `Voting::ClassBalanced` divides by. Other
+/// voting modes ignore them.
+pub fn vote_with_frequencies(neighbors: Vec<MeasuredFlower>, voting: Voting, frequencies: &HashMap<String, usize>) -> Option<String> {
+    tally_votes(neighbors, voting, frequencies).into_iter().next().map(|(class, _)| class)
+}
+
+/// Number of rows in each class of `train`.
+pub fn class_frequencies(train: &[Sample]) -> HashMap<String, usize> {
+    let mut frequencies = HashMap::new();
+    for row in train {
+        *frequencies.entry(row.class.clone()).or_default() += 1;
+    }
+    frequencies
+}
+
+/// The class frequencies `voting` needs from `train`: empty unless it is
+/// `Voting::ClassBalanced`, to skip counting when they aren't used.
+fn frequencies_for(train: &[Sample], voting: Voting) -> HashMap<String, usize> {
+    if voting == Voting::ClassBalanced {
+        class_frequencies(train)
+    } else {
+        HashMap::new()
+    }
+}
+
+/// Every class among `neighbors`, best first, ranked by the same rules
+/// `vote_with_frequencies` uses to pick the winner, including its use of
+/// `frequencies`.
+pub fn rank_classes(neighbors: Vec<MeasuredFlower>, voting: Voting, frequencies: &HashMap<String, usize>) -> Vec<String> {
+    tally_votes(neighbors, voting, frequencies).into_iter().map(|(class, _)| class).collect()
+}
+
+/// Each class with its summed vote weight, in `rank_classes` order.
+/// `frequencies` only matter for `Voting::ClassBalanced`.
+fn tally_votes(neighbors: Vec<MeasuredFlower>, voting: Voting, frequencies: &HashMap<String, usize>) -> Vec<(String, f64)> {
+    // class -> (summed vote weight, distance of its nearest neighbor)
+    let mut m: HashMap<String, (f64, f64)> = HashMap::new();
+    for x in neighbors {
+        let mut weight = voting.weight(x.distance);
+        if voting == Voting::ClassBalanced {
+            if let Some(&count) = frequencies.get(&x.class) {
+                weight /= count as f64;
+            }
+        }
+        let entry = m.entry(x.class).or_insert((0.0, f64::INFINITY));
+        entry.0 += weight;
+        entry.1 = entry.1.min(x.distance);
+    }
+    let mut ranked: Vec<(String, (f64, f64))> = m.into_iter().collect();
+    ranked.sort_by(|(class_a, (votes_a, nearest_a)), (class_b, (votes_b, nearest_b))| {
+        votes_b.total_cmp(votes_a)
+            .then_with(|| nearest_a.total_cmp(nearest_b))
+            .then_with(|| class_a.cmp(class_b))
+    });
+    ranked.into_iter().map(|(class, (votes, _))| (class, votes)).collect()
+}
+
+/// Every class among the nearest neighbors with its share of the vote
+/// weight, best first. With `Voting::Majority` that is the fraction of the
+/// neighbors in the class. The probabilities sum to 1.0, and the first entry
+/// is what `predict_classification` returns. Fails as
+/// `predict_classification` does.
+pub fn predict_proba(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<Vec<(String, f64)>, KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    check_voting(voting)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    let tally = tally_votes(neighbors, voting, &frequencies_for(train, voting));
+    let total: f64 = tally.iter().map(|(_, votes)| votes).sum();
+    Ok(tally.into_iter().map(|(class, votes)| (class, votes / total)).collect())
+}
+
+/// One-vs-rest scores: for every class in `train`, the fraction of the
+/// nearest neighbors that belong to it, so classes with no neighbors score
+/// 0.0. Unlike `predict_proba` each score is independent of the voting
+/// scheme. Fails as `predict_classification` does.
+pub fn decision_scores(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric) -> Result<HashMap<String, f64>, KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    let mut scores: HashMap<String, f64> = train.iter().map(|row| (row.class.clone(), 0.0)).collect();
+    for neighbor in &neighbors {
+        *scores.get_mut(&neighbor.class).expect("neighbors come from train") += 1.0 / neighbors.len() as f64;
+    }
+    Ok(scores)
+}
+
+/// Candidate classes for `test_row`, best first, for use with
+/// `top_k_accuracy`. The first entry is what `predict_classification` returns.
+/// Fails as `predict_classification` does.
+pub fn predict_ranked(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<Vec<String>, KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    check_voting(voting)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    Ok(rank_classes(neighbors, voting, &frequencies_for(train, voting)))
+}
+
+/// The `num_neighbors` training rows closest to `test_row`, nearest first.
+/// A `num_neighbors` larger than `train` is clamped: every row is returned.
+pub fn get_neighbors<L: Clone, F: Feature>(train: &[Sample<L, F>], test_row: &Sample<L, F>, num_neighbors: usize, metric: DistanceMetric) -> Vec<MeasuredFlower<L>> {
+    if metric != DistanceMetric::Euclidean {
+        return get_neighbors_with(train, test_row, num_neighbors, |a, b| distance(metric, a, b));
+    }
+
+    // Squared distances rank identically, so the scan skips the sqrt and it is
+    // only taken for the neighbors that are returned.
+    let mut neighbors = get_neighbors_with(train, test_row, num_neighbors, squared_euclidean_distance);
+    for neighbor in neighbors.iter_mut() {
+        neighbor.distance = neighbor.distance.sqrt();
+    }
+    neighbors
+}
+
+/// Like `get_neighbors`, but reports a search that can't return
+/// `num_neighbors` valid neighbors as an error instead of clamping: an empty
+/// training set, a `num_neighbors` larger than it, a `Minkowski` exponent
+/// that isn't positive, or a NaN distance among the nearest.
+///
+/// The `predict_*` functions and `KnnClassifier` search through this.
+pub fn try_get_neighbors<L: Clone, F: Feature>(train: &[Sample<L, F>], test_row: &Sample<L, F>, num_neighbors: usize, metric: DistanceMetric) -> Result<Vec<MeasuredFlower<L>>, KnnError> {
+    check_search(train.len(), num_neighbors)?;
+    check_metric(metric)?;
+    check_distances(get_neighbors(train, test_row, num_neighbors, metric))
+}
+
+/// Whether `num_neighbors` can be found among `n_rows` training rows.
+fn check_search(n_rows: usize, num_neighbors: usize) -> Result<(), KnnError> {
+    if n_rows == 0 {
+        return Err(KnnError::EmptyTraining);
+    }
+    if num_neighbors > n_rows {
+        return Err(KnnError::KTooLarge { k: num_neighbors, n_rows });
+    }
+    Ok(())
+}
+
+/// Rejects the `metric` parameters that would make `distance` panic.
+fn check_metric(metric: DistanceMetric) -> Result<(), KnnError> {
+    match metric {
+        DistanceMetric::Minkowski(p) if p.is_nan() || p <= 0.0 => Err(KnnError::InvalidMinkowskiP(p)),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects the `voting` parameters that would make `Voting::weight` panic.
+fn check_voting(voting: Voting) -> Result<(), KnnError> {
+    match voting {
+        Voting::Kernel { bandwidth } if bandwidth.is_nan() || bandwidth <= 0.0 => Err(KnnError::InvalidBandwidth(bandwidth)),
+        _ => Ok(()),
+    }
+}
+
+/// Passes `neighbors` through unless one has a NaN distance.
+fn check_distances<L>(neighbors: Vec<MeasuredFlower<L>>) -> Result<Vec<MeasuredFlower<L>>, KnnError> {
+    // NaN distances sort last, so checking the farthest is enough.
+    if neighbors.last().is_some_and(|neighbor| neighbor.distance.is_nan()) {
+        return Err(KnnError::NanDistance);
+    }
+    Ok(neighbors)
+}
+
+/// Like `get_neighbors`, but measures distance with any function, for
+/// domain-specific distances the `DistanceMetric` enum doesn't cover.
+pub fn get_neighbors_with<L, T, F>(train: &[Sample<L, T>], test_row: &Sample<L, T>, num_neighbors: usize, distance_fn: F) -> Vec<MeasuredFlower<L>>
+where
+    L: Clone,
+    F: Fn(&Sample<L, T>, &Sample<L, T>) -> f64,
+{
+    // Stream the rows through a bounded heap so only the nearest
+    // `num_neighbors` are ever held.
+    let mut nearest = TopK::new(num_neighbors);
+    for train_row in train {
+        let dist = distance_fn(test_row, train_row);
+        nearest.push(MeasuredFlower {distance: dist, class: train_row.class.clone()});
+    }
+    nearest.into_sorted_vec()
+}
+
+/// Every training row as a neighbor of `test_row`, yielded nearest first.
+/// Distances are all computed up front, but ordering is lazy: they are
+/// heapified in O(n) and each `next` pops one in O(log n), so taking the
+/// first few or stopping at a distance threshold never sorts the rest.
+pub fn neighbors_iter<L: Clone, F: Feature>(train: &[Sample<L, F>], test_row: &Sample<L, F>, metric: DistanceMetric) -> NeighborsIter<L> {
+    let neighbors: Vec<Reverse<ByDistance<L>>> = train.iter()
+        .map(|train_row| Reverse(ByDistance(MeasuredFlower {
+            distance: distance(metric, test_row, train_row),
+            class: train_row.class.clone(),
+        })))
+        .collect();
+    NeighborsIter { heap: BinaryHeap::from(neighbors) }
+}
+
+/// Iterator returned by `neighbors_iter`.
+pub struct NeighborsIter<L = String> {
+    heap: BinaryHeap<Reverse<ByDistance<L>>>,
+}
+
+impl<L> Iterator for NeighborsIter<L> {
+    type Item = MeasuredFlower<L>;
+
+    fn next(&mut self) -> Option<MeasuredFlower<L>> {
+        self.heap.pop().map(|Reverse(ByDistance(neighbor))| neighbor)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl<L> ExactSizeIterator for NeighborsIter<L> {}
+
+/// The `num_neighbors` smallest entries of `distances`, nearest first.
+pub(crate) fn select_nearest<L>(mut distances: Vec<MeasuredFlower<L>>, num_neighbors: usize) -> Vec<MeasuredFlower<L>> {
+    // Only the nearest `num_neighbors` need ordering: partition them to the
+    // front in O(n), then sort just that prefix.
+    let k = num_neighbors.min(distances.len());
+    if k > 0 && k < distances.len() {
+        distances.select_nth_unstable_by(k - 1, |a, b| compare_distances(a.distance, b.distance));
+    }
+    distances.truncate(k);
+    distances.sort_by(|a, b| compare_distances(a.distance, b.distance));
+    distances
+}
+
+/// Predicts a numeric target as the mean of the neighbors' targets, weighted
+/// according to `voting` (`Voting::InverseDistance` gives a distance-weighted
+/// mean). Fails as `predict_classification` does.
+pub fn predict_regression(train: &[RegressionSample], test_row: &RegressionSample, num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<f64, KnnError> {
+    check_prediction(train, test_row, num_neighbors)?;
+    check_voting(voting)?;
+    let neighbors = try_get_neighbors(train, test_row, num_neighbors, metric)?;
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for x in neighbors {
+        let weight = voting.weight(x.distance);
+        weighted_sum += weight * x.class;
+        total_weight += weight;
+    }
+    Ok(weighted_sum / total_weight)
+}
+
+/// Every training row within `radius` of `test_row` (inclusive), nearest
+/// first. The result may be empty; pass a non-empty set to `vote` to get a
+/// prediction.
+pub fn neighbors_within_radius<L: Clone, F: Feature>(train: &[Sample<L, F>], test_row: &Sample<L, F>, radius: f64, metric: DistanceMetric) -> Vec<MeasuredFlower<L>> {
+    let mut neighbors: Vec<MeasuredFlower<L>> = train.iter()
+        .map(|train_row| MeasuredFlower {
+            distance: distance(metric, test_row, train_row),
+            class: train_row.class.clone(),
+        })
+        .filter(|neighbor| neighbor.distance <= radius)
+        .collect();
+    neighbors.sort_by(|a, b| compare_distances(a.distance, b.distance));
+    neighbors
+}
+
+/// Orders distances ascending, with NaN after every real value so a bad row
+/// can never be picked ahead of a valid one.
+pub(crate) fn compare_distances(a: f64, b: f64) -> Ordering {
+    a.is_nan().cmp(&b.is_nan()).then_with(|| a.total_cmp(&b))
+}
+
+/// Panic message for a vote that the checks before it make impossible.
+const NO_NEIGHBORS: &str = "no neighbors to vote with: the training set is empty or num_neighbors is 0";
+
+/// Class labels of the nearest neighbors, nearest first.
+pub fn neighbor_classes(train: &[Sample], test_row: &Sample, num_neighbors: usize, metric: DistanceMetric) -> Vec<String> {
+    get_neighbors(train, test_row, num_neighbors, metric)
+        .into_iter()
+        .map(|neighbor| neighbor.class)
+        .collect()
+}
+
+/// Indices into `train` of the nearest neighbors, nearest first, for when
+/// the caller needs the rows themselves rather than their labels.
+pub fn neighbor_indices<L, F: Feature>(train: &[Sample<L, F>], test_row: &Sample<L, F>, num_neighbors: usize, metric: DistanceMetric) -> Vec<usize> {
+    let mut nearest = TopK::new(num_neighbors);
+    for (i, train_row) in train.iter().enumerate() {
+        nearest.push(MeasuredFlower { distance: distance(metric, test_row, train_row), class: i });
+    }
+    nearest.into_sorted_vec()
+        .into_iter()
+        .map(|neighbor| neighbor.class)
+        .collect()
+}
+
+/// Predicts every row of `test`. With the `parallel` feature the rows are
+/// spread across threads; the output order always matches `test`.
+///
+/// Fails with the error of the first row `predict_classification` fails for.
+#[cfg(feature = "parallel")]
+pub fn k_nearest_neighbors(train: &[Sample], test: &[Sample], num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<Vec<String>, KnnError> {
+    use rayon::prelude::*;
+
+    test.par_iter()
+        .map(|row| predict_classification(train, row, num_neighbors, metric, voting))
+        .collect()
+}
+
+/// Predicts every row of `test`. With the `parallel` feature the rows are
+/// spread across threads; the output order always matches `test`.
+///
+/// Fails with the error of the first row `predict_classification` fails for.
+#[cfg(not(feature = "parallel"))]
+pub fn k_nearest_neighbors(train: &[Sample], test: &[Sample], num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> Result<Vec<String>, KnnError> {
+    let mut predictions = Vec::new();
+    for row in test {
+        let output = predict_classification(train, row, num_neighbors, metric, voting)?;
+        predictions.push(output);
+    }
+    Ok(predictions)
+
+}
+
+/// Above this many features `KnnClassifier` prefers a `BallTree`, since k-d
+/// tree pruning on single axes weakens as dimensions grow.
+pub const KD_TREE_MAX_FEATURES: usize = 10;
+
+/// How a `KnnClassifier` finds neighbors among its training rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SearchIndex {
+    BruteForce(Vec<Sample>),
+    KdTree(KdTree),
+    BallTree(BallTree),
+    #[cfg(feature = "ndarray")]
+    Dense(DenseIndex),
+    /// A linear scan measuring Mahalanobis distance, whatever the metric.
+    Mahalanobis {
+        train: Vec<Sample>,
+        inverse_covariance: Vec<Vec<f64>>,
+    },
+}
+
+impl SearchIndex {
+    /// The nearest neighbors of `row`, with the same checks as
+    /// `try_get_neighbors`.
+    fn nearest(&self, row: &Sample, num_neighbors: usize, metric: DistanceMetric) -> Result<Vec<MeasuredFlower>, KnnError> {
+        check_search(self.len(), num_neighbors)?;
+        check_metric(metric)?;
+        let neighbors = match self {
+            SearchIndex::BruteForce(train) => get_neighbors(train, row, num_neighbors, metric),
+            SearchIndex::KdTree(tree) => tree.nearest(row, num_neighbors),
+            SearchIndex::BallTree(tree) => tree.nearest(row, num_neighbors),
+            #[cfg(feature = "ndarray")]
+            SearchIndex::Dense(index) => index.nearest(row, num_neighbors),
+            SearchIndex::Mahalanobis { train, inverse_covariance } => {
+                get_neighbors_with(train, row, num_neighbors, |a, b| mahalanobis_distance(a, b, inverse_covariance))
+            }
+        };
+        check_distances(neighbors)
+    }
+
+    /// Features per training row, or 0 with no training rows.
+    fn n_features(&self) -> usize {
+        let first = match self {
+            SearchIndex::BruteForce(train) | SearchIndex::Mahalanobis { train, .. } => train.first(),
+            SearchIndex::KdTree(tree) => tree.points().first(),
+            SearchIndex::BallTree(tree) => tree.points().first(),
+            #[cfg(feature = "ndarray")]
+            SearchIndex::Dense(index) => return index.n_features(),
+        };
+        first.map_or(0, |row| row.features.len())
+    }
+
+    /// Number of training rows.
+    fn len(&self) -> usize {
+        match self {
+            SearchIndex::BruteForce(train) | SearchIndex::Mahalanobis { train, .. } => train.len(),
+            SearchIndex::KdTree(tree) => tree.points().len(),
+            SearchIndex::BallTree(tree) => tree.points().len(),
+            #[cfg(feature = "ndarray")]
+            SearchIndex::Dense(index) => index.len(),
+        }
+    }
+
+    /// A linear scan over `data`: vectorized with the `ndarray` feature.
+    #[cfg(feature = "ndarray")]
+    fn scan(data: Vec<Sample>, metric: DistanceMetric) -> SearchIndex {
+        SearchIndex::Dense(DenseIndex::new(&data, metric))
+    }
+
+    /// A linear scan over `data`: vectorized with the `ndarray` feature.
+    #[cfg(not(feature = "ndarray"))]
+    fn scan(data: Vec<Sample>, _metric: DistanceMetric) -> SearchIndex {
+        SearchIndex::BruteForce(data)
+    }
+}
+
+/// A k-nearest-neighbors model holding its training rows and settings.
+/// `fit` builds a `KdTree` when the metric allows it and there are at most
+/// `KD_TREE_MAX_FEATURES` features, and otherwise a `BallTree` if the metric
+/// allows that. The rest fall back to a linear scan, which the `ndarray`
+/// feature runs over a `DenseIndex`. A fitted model can be saved to and
+/// loaded from JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnnClassifier {
+    index: SearchIndex,
+    num_neighbors: usize,
+    metric: DistanceMetric,
+    voting: Voting,
+    scaler: Option<Scaler>,
+    /// Training-set class counts, for `Voting::ClassBalanced`.
+    #[serde(default)]
+    class_frequencies: HashMap<String, usize>,
+    #[serde(default)]
+    mahalanobis: bool,
+}
+
+impl KnnClassifier {
+    pub fn new(num_neighbors: usize, metric: DistanceMetric, voting: Voting) -> KnnClassifier {
+        KnnClassifier {
+            index: SearchIndex::BruteForce(Vec::new()),
+            num_neighbors,
+            metric,
+            voting,
+            scaler: None,
+            class_frequencies: HashMap::new(),
+            mahalanobis: false,
+        }
+    }
+
+    /// Scales features with `scaler`: `fit` learns its parameters from the
+    /// training rows and `predict` applies them to every query.
+    pub fn with_scaler(mut self, scaler: Scaler) -> KnnClassifier {
+        self.scaler = Some(scaler);
+        self
+    }
+
+    /// Measures distance with `mahalanobis_distance` instead of the metric,
+    /// using the inverse covariance of the (scaled) training rows estimated
+    /// by `fit`. Suits features that are correlated with each other.
+    pub fn with_mahalanobis(mut self) -> KnnClassifier {
+        self.mahalanobis = true;
+        self
+    }
+
+    /// Replaces the training set and builds the search index described on
+    /// `KnnClassifier`.
+    ///
+    /// Fails with `DimensionMismatch`, leaving the model unchanged, if the
+    /// rows don't all have the same number of features as the first. With
+    /// `with_mahalanobis`, fails with `SingularCovariance` if the training
+    /// features are linearly dependent, leaving the model unchanged apart
+    /// from a fitted scaler.
+    pub fn fit(&mut self, mut data: Vec<Sample>) -> Result<(), KnnError> {
+        let n_features = data.first().map_or(0, |row| row.features.len());
+        if let Some(row) = data.iter().find(|row| row.features.len() != n_features) {
+            return Err(KnnError::DimensionMismatch { expected: n_features, found: row.features.len() });
+        }
+        if let Some(scaler) = self.scaler.as_mut() {
+            scaler.fit(&data);
+            scaler.transform(&mut data);
+        }
+        let frequencies = class_frequencies(&data);
+        self.index = if self.mahalanobis {
+            let inverse_covariance = inverse_covariance(&data).ok_or(KnnError::SingularCovariance)?;
+            SearchIndex::Mahalanobis { train: data, inverse_covariance }
+        } else if KdTree::supports(self.metric) && n_features <= KD_TREE_MAX_FEATURES {
+            SearchIndex::KdTree(KdTree::new(data, self.metric))
+        } else if BallTree::supports(self.metric) {
+            SearchIndex::BallTree(BallTree::new(data, self.metric))
+        } else {
+            SearchIndex::scan(data, self.metric)
+        };
+        self.class_frequencies = frequencies;
+        Ok(())
+    }
+
+    /// Fails with `EmptyTraining` before `fit` or after fitting no rows,
+    /// when `num_neighbors` is 0, when `row` has a different number of
+    /// features than the training rows, on an invalid kernel bandwidth as in
+    /// `predict_classification`, or when the search does, as in
+    /// `try_get_neighbors`.
+    pub fn predict(&self, row: &Sample) -> Result<String, KnnError> {
+        if self.index.len() == 0 {
+            return Err(KnnError::EmptyTraining);
+        }
+        if self.num_neighbors == 0 {
+            return Err(KnnError::InvalidK(self.num_neighbors));
+        }
+        let expected = self.index.n_features();
+        if row.features.len() != expected {
+            return Err(KnnError::DimensionMismatch { expected, found: row.features.len() });
+        }
+        check_voting(self.voting)?;
+        let neighbors = match &self.scaler {
+            Some(scaler) => {
+                let mut scaled = [row.clone()];
+                scaler.transform(&mut scaled);
+                self.index.nearest(&scaled[0], self.num_neighbors, self.metric)?
+            }
+            None => self.index.nearest(row, self.num_neighbors, self.metric)?,
+        };
+        Ok(vote_with_frequencies(neighbors, self.voting, &self.class_frequencies).expect(NO_NEIGHBORS))
+    }
+
+    /// Writes the model, including its training rows, as JSON.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Reads a model written by `save`.
+    pub fn load(path: &str) -> Result<KnnClassifier, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Predicts every row, across threads with the `parallel` feature.
+    ///
+    /// Fails with the error of the first row `predict` fails for.
+    #[cfg(feature = "parallel")]
+    pub fn predict_batch(&self, rows: &[Sample]) -> Result<Vec<String>, KnnError> {
+        use rayon::prelude::*;
+
+        rows.par_iter().map(|row| self.predict(row)).collect()
+    }
+
+    /// Predicts every row, across threads with the `parallel` feature.
+    ///
+    /// Fails with the error of the first row `predict` fails for.
+    #[cfg(not(feature = "parallel"))]
+    pub fn predict_batch(&self, rows: &[Sample]) -> Result<Vec<String>, KnnError> {
+        rows.iter().map(|row| self.predict(row)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    fn sample(features: &[f64], class: &str) -> Sample {
+        Sample::new(features.to_vec(), class)
+    }
+
+    fn measured(distance: f64, class: &str) -> MeasuredFlower {
+        MeasuredFlower { distance, class: class.to_string() }
+    }
+
+    fn two_classes() -> Vec<Sample> {
+        vec![
+            sample(&[0.0, 0.0], "a"),
+            sample(&[0.0, 1.0], "a"),
+            sample(&[5.0, 5.0], "b"),
+            sample(&[5.0, 6.0], "b"),
+        ]
+    }
+
+    #[test]
+    fn try_get_neighbors_rejects_empty_training() {
+        let result = try_get_neighbors(&[], &sample(&[0.0, 0.0], "a"), 1, DistanceMetric::Euclidean);
+        assert!(matches!(result, Err(KnnError::EmptyTraining)));
+    }
+
+    #[test]
+    fn try_get_neighbors_rejects_k_larger_than_training() {
+        let result = try_get_neighbors(&two_classes(), &sample(&[0.0, 0.0], "a"), 5, DistanceMetric::Euclidean);
+        assert!(matches!(result, Err(KnnError::KTooLarge { k: 5, n_rows: 4 })));
+    }
+
+    #[test]
+    fn try_get_neighbors_rejects_nan_distance() {
+        let mut train = two_classes();
+        train.push(sample(&[f64::NAN, 0.0], "b"));
+        let result = try_get_neighbors(&train, &sample(&[0.0, 0.0], "a"), 5, DistanceMetric::Euclidean);
+        assert!(matches!(result, Err(KnnError::NanDistance)));
+    }
+
+    #[test]
+    fn predict_classification_reports_each_error() {
+        let train = two_classes();
+        let row = sample(&[0.0, 0.0], "a");
+        let euclidean = DistanceMetric::Euclidean;
+        assert!(matches!(predict_classification(&[], &row, 1, euclidean, Voting::Majority), Err(KnnError::EmptyTraining)));
+        assert!(matches!(predict_classification(&train, &row, 0, euclidean, Voting::Majority), Err(KnnError::InvalidK(0))));
+        assert!(matches!(predict_classification(&train, &row, 9, euclidean, Voting::Majority), Err(KnnError::KTooLarge { .. })));
+        assert!(matches!(
+            predict_classification(&train, &sample(&[0.0], "a"), 1, euclidean, Voting::Majority),
+            Err(KnnError::DimensionMismatch { expected: 2, found: 1 })
+        ));
+        assert!(matches!(
+            predict_classification(&train, &sample(&[f64::NAN, 0.0], "a"), 1, euclidean, Voting::Majority),
+            Err(KnnError::NanDistance)
+        ));
+        assert!(matches!(
+            predict_classification(&train, &row, 1, DistanceMetric::Minkowski(-1.0), Voting::Majority),
+            Err(KnnError::InvalidMinkowskiP(p)) if p == -1.0
+        ));
+        assert!(matches!(
+            predict_classification(&train, &row, 1, euclidean, Voting::Kernel { bandwidth: 0.0 }),
+            Err(KnnError::InvalidBandwidth(bandwidth)) if bandwidth == 0.0
+        ));
+        assert_eq!(predict_classification(&train, &row, 3, euclidean, Voting::Majority).unwrap(), "a");
+    }
+
+    #[test]
+    fn k_nearest_neighbors_returns_the_first_error() {
+        let train = two_classes();
+        let test = [sample(&[0.0, 0.0], "a"), sample(&[0.0], "a")];
+        let result = k_nearest_neighbors(&train, &test, 1, DistanceMetric::Euclidean, Voting::Majority);
+        assert!(matches!(result, Err(KnnError::DimensionMismatch { expected: 2, found: 1 })));
+        let predicted = k_nearest_neighbors(&train, &test[..1], 1, DistanceMetric::Euclidean, Voting::Majority).unwrap();
+        assert_eq!(predicted, ["a"]);
+    }
+
+    /// Two classes running side by side along the diagonal, closer to each
+    /// other across it than successive rows are along it.
+    fn correlated_classes(offset: f64) -> Vec<Sample> {
+        (0..20)
+            .flat_map(|t| {
+                let t = t as f64 + offset;
+                [sample(&[t, t + 0.4], "a"), sample(&[t, t - 0.4], "b")]
+            })
+            .collect()
+    }
+
+    fn classifier_accuracy(model: &KnnClassifier, test: &[Sample]) -> f64 {
+        let actual: Vec<String> = test.iter().map(|row| row.class.clone()).collect();
+        crate::metrics::accuracy_metric(&actual, &model.predict_batch(test).unwrap())
+    }
+
+    #[test]
+    fn mahalanobis_separates_correlated_classes() {
+        let test = correlated_classes(0.5);
+        let mut euclidean = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority);
+        euclidean.fit(correlated_classes(0.0)).unwrap();
+        let mut mahalanobis = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority).with_mahalanobis();
+        mahalanobis.fit(correlated_classes(0.0)).unwrap();
+
+        let euclidean_accuracy = classifier_accuracy(&euclidean, &test);
+        let mahalanobis_accuracy = classifier_accuracy(&mahalanobis, &test);
+        assert_eq!(mahalanobis_accuracy, 100.0);
+        assert!(mahalanobis_accuracy > euclidean_accuracy, "{} vs {}", mahalanobis_accuracy, euclidean_accuracy);
+    }
+
+    #[test]
+    fn mahalanobis_fit_rejects_a_constant_column() {
+        let train = vec![sample(&[1.0, 2.0], "a"), sample(&[1.0, 3.0], "a"), sample(&[1.0, 5.0], "b")];
+        let mut model = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority).with_mahalanobis();
+        assert!(matches!(model.fit(train), Err(KnnError::SingularCovariance)));
+    }
+
+    #[test]
+    fn adaptive_k_uses_fewer_neighbors_in_dense_regions() {
+        // A tight cluster around the origin and a sparse row of points far off.
+        let mut train: Vec<Sample> = (0..10).map(|i| sample(&[i as f64 * 0.1, 0.0], "a")).collect();
+        train.extend((0..10).map(|i| sample(&[100.0 + i as f64 * 3.0, 0.0], "b")));
+        let params = AdaptiveK {
+            metric: DistanceMetric::Euclidean,
+            voting: Voting::Majority,
+            distance_scale: 4.0,
+            min_neighbors: 1,
+            max_neighbors: 9,
+        };
+
+        let (dense_class, dense_k) = predict_adaptive(&train, &sample(&[0.45, 0.0], "a"), &params).unwrap();
+        let (sparse_class, sparse_k) = predict_adaptive(&train, &sample(&[107.5, 0.0], "b"), &params).unwrap();
+        assert_eq!((dense_class.as_str(), sparse_class.as_str()), ("a", "b"));
+        assert_eq!(dense_k, 1);
+        assert_eq!(sparse_k, 6);
+        assert!(dense_k < sparse_k);
+    }
+
+    #[test]
+    fn rank_classes_uses_class_frequencies() {
+        let neighbors = vec![measured(1.0, "common"), measured(1.0, "common"), measured(1.0, "rare")];
+        let frequencies = HashMap::from([("common".to_string(), 10), ("rare".to_string(), 1)]);
+        assert_eq!(rank_classes(neighbors.clone(), Voting::ClassBalanced, &frequencies), ["rare", "common"]);
+        assert_eq!(rank_classes(neighbors, Voting::Majority, &frequencies), ["common", "rare"]);
+    }
+
+    #[test]
+    fn classifier_reports_search_errors() {
+        let row = sample(&[0.0, 0.0], "a");
+        let unfitted = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority);
+        assert!(matches!(unfitted.predict(&row), Err(KnnError::EmptyTraining)));
+        let unfitted = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority).with_scaler(Scaler::new());
+        assert!(matches!(unfitted.predict(&row), Err(KnnError::EmptyTraining)));
+
+        let mut zero_k = KnnClassifier::new(0, DistanceMetric::Euclidean, Voting::Majority);
+        zero_k.fit(two_classes()).unwrap();
+        assert!(matches!(zero_k.predict(&row), Err(KnnError::InvalidK(0))));
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Cosine] {
+            let mut too_many = KnnClassifier::new(5, metric, Voting::Majority);
+            too_many.fit(two_classes()).unwrap();
+            assert!(matches!(too_many.predict(&row), Err(KnnError::KTooLarge { k: 5, n_rows: 4 })));
+        }
+
+        let mut model = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority);
+        model.fit(two_classes()).unwrap();
+        let mut ragged = two_classes();
+        ragged[2].features.push(1.0);
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Canberra, DistanceMetric::Cosine] {
+            let mut refit = KnnClassifier::new(1, metric, Voting::Majority);
+            assert!(matches!(refit.fit(ragged.clone()), Err(KnnError::DimensionMismatch { expected: 2, found: 3 })));
+            assert!(matches!(refit.predict(&row), Err(KnnError::EmptyTraining)));
+        }
+        let mut kernel = KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Kernel { bandwidth: -1.0 });
+        kernel.fit(two_classes()).unwrap();
+        assert!(matches!(kernel.predict(&row), Err(KnnError::InvalidBandwidth(_))));
+        for p in [0.0, -1.0, f64::NAN] {
+            let mut minkowski = KnnClassifier::new(1, DistanceMetric::Minkowski(p), Voting::Majority);
+            minkowski.fit(two_classes()).unwrap();
+            assert!(matches!(minkowski.predict(&row), Err(KnnError::InvalidMinkowskiP(_))));
+        }
+        let batch = [row.clone(), sample(&[0.0], "a"), row];
+        assert!(matches!(model.predict_batch(&batch), Err(KnnError::DimensionMismatch { expected: 2, found: 1 })));
+    }
+
+    #[test]
+    fn classifier_rejects_a_query_of_the_wrong_length_on_every_index() {
+        let models = [
+            // k-d tree, ball tree and linear scan.
+            KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority),
+            KnnClassifier::new(1, DistanceMetric::Canberra, Voting::Majority),
+            KnnClassifier::new(1, DistanceMetric::Cosine, Voting::Majority),
+            KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority).with_mahalanobis(),
+            KnnClassifier::new(1, DistanceMetric::Euclidean, Voting::Majority).with_scaler(Scaler::new()),
+        ];
+        for mut model in models {
+            model.fit(two_classes()).unwrap();
+            assert!(matches!(model.predict(&sample(&[5.0], "b")), Err(KnnError::DimensionMismatch { expected: 2, found: 1 })));
+            assert!(matches!(model.predict(&sample(&[5.0, 5.0, 5.0], "b")), Err(KnnError::DimensionMismatch { expected: 2, found: 3 })));
+            assert_eq!(model.predict(&sample(&[5.0, 5.5], "b")).unwrap(), "b");
+        }
+    }
+
+    #[test]
+    fn tied_votes_go_to_the_nearest_class_then_the_smallest_name() {
+        let neighbors = vec![measured(1.0, "a"), measured(0.5, "b"), measured(1.5, "a"), measured(2.0, "b")];
+        for _ in 0..10 {
+            assert_eq!(vote(neighbors.clone(), Voting::Majority).unwrap(), "b");
+        }
+        let equally_near = vec![measured(1.0, "b"), measured(1.0, "a"), measured(2.0, "a"), measured(2.0, "b")];
+        assert_eq!(vote(equally_near, Voting::Majority).unwrap(), "a");
+    }
+
+    /// Random rows in three classes.
+    fn random_rows(seed: u64, n_rows: usize) -> Vec<Sample> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n_rows)
+            .map(|i| {
+                let features = (0..3).map(|_| rng.gen_range(-5.0..5.0)).collect();
+                Sample { features, class: ["a", "b", "c"][i % 3].to_string() }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn get_neighbors_returns_ascending_distances() {
+        let train = random_rows(7, 50);
+        let query = sample(&[0.0, 0.0, 0.0], "a");
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Cosine] {
+            let neighbors = get_neighbors(&train, &query, 10, metric);
+            assert_eq!(neighbors.len(), 10);
+            assert!(neighbors.windows(2).all(|pair| pair[0].distance <= pair[1].distance), "{:?}", metric);
+        }
+    }
+
+    #[test]
+    fn inverse_distance_lets_a_close_neighbor_outvote_distant_ones() {
+        let neighbors = vec![measured(0.1, "rare"), measured(2.0, "common"), measured(3.0, "common")];
+        assert_eq!(vote(neighbors.clone(), Voting::Majority).unwrap(), "common");
+        assert_eq!(vote(neighbors, Voting::InverseDistance).unwrap(), "rare");
+    }
+
+    #[test]
+    fn select_nearest_matches_a_full_sort() {
+        let train = random_rows(9, 200);
+        let query = sample(&[1.0, -1.0, 0.5], "a");
+        let mut sorted: Vec<MeasuredFlower> = train.iter()
+            .map(|row| MeasuredFlower { distance: distance(DistanceMetric::Euclidean, &query, row), class: row.class.clone() })
+            .collect();
+        let distances = sorted.clone();
+        sorted.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        for k in [0, 1, 10, 200, 250] {
+            let selected: Vec<f64> = select_nearest(distances.clone(), k).iter().map(|n| n.distance).collect();
+            let expected: Vec<f64> = sorted.iter().take(k).map(|n| n.distance).collect();
+            assert_eq!(selected, expected, "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn nan_distances_sort_after_valid_ones() {
+        let mut train = two_classes();
+        train.insert(0, sample(&[f64::NAN, 0.0], "b"));
+        let neighbors = get_neighbors(&train, &sample(&[0.0, 0.0], "a"), 5, DistanceMetric::Euclidean);
+        assert!(neighbors[..4].iter().all(|n| !n.distance.is_nan()));
+        assert!(neighbors[4].distance.is_nan());
+
+        let nearest = get_neighbors(&train, &sample(&[0.0, 0.0], "a"), 2, DistanceMetric::Euclidean);
+        assert_eq!(nearest.iter().map(|n| n.class.as_str()).collect::<Vec<_>>(), ["a", "a"]);
+        assert_eq!(compare_distances(f64::NAN, f64::INFINITY), Ordering::Greater);
+        assert_eq!(compare_distances(f64::NAN, f64::NAN), Ordering::Equal);
+    }
+
+    #[test]
+    fn classifier_fits_and_predicts_like_predict_classification() {
+        let train = random_rows(11, 60);
+        let test = random_rows(12, 15);
+        let mut model = KnnClassifier::new(5, DistanceMetric::Euclidean, Voting::Majority);
+        model.fit(train.clone()).unwrap();
+
+        let batch = model.predict_batch(&test).unwrap();
+        assert_eq!(batch.len(), test.len());
+        for (row, predicted) in test.iter().zip(&batch) {
+            assert_eq!(&model.predict(row).unwrap(), predicted);
+            assert_eq!(predicted, &predict_classification(&train, row, 5, DistanceMetric::Euclidean, Voting::Majority).unwrap());
+        }
+
+        // Refitting replaces the training set.
+        model.fit(two_classes()).unwrap();
+        assert!(matches!(model.predict(&sample(&[5.0, 5.5], "b")), Err(KnnError::KTooLarge { k: 5, n_rows: 4 })));
+    }
+
+    #[test]
+    fn regression_averages_neighbor_targets_on_linear_data() {
+        let train: Vec<RegressionSample> = (0..20)
+            .map(|x| Sample { features: vec![x as f64], class: 2.0 * x as f64 + 1.0 })
+            .collect();
+        let query = |x: f64| Sample { features: vec![x], class: 0.0 };
+        let euclidean = DistanceMetric::Euclidean;
+
+        assert_eq!(predict_regression(&train, &query(10.0), 3, euclidean, Voting::Majority).unwrap(), 21.0);
+        assert_eq!(predict_regression(&train, &query(7.5), 2, euclidean, Voting::Majority).unwrap(), 16.0);
+        let weighted = predict_regression(&train, &query(7.2), 2, euclidean, Voting::InverseDistance).unwrap();
+        assert!((weighted - 15.4).abs() < 1e-6, "{}", weighted);
+    }
+
+    #[test]
+    fn euclidean_neighbors_rank_by_squared_distance() {
+        let train = random_rows(28, 40);
+        let query = sample(&[0.5, 0.5, 0.5], "a");
+        let neighbors = get_neighbors(&train, &query, 40, DistanceMetric::Euclidean);
+
+        let mut by_squared: Vec<f64> = train.iter().map(|row| squared_euclidean_distance(&query, row)).collect();
+        by_squared.sort_by(f64::total_cmp);
+        for (neighbor, squared) in neighbors.iter().zip(&by_squared) {
+            assert!((neighbor.distance - squared.sqrt()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn k_nearest_neighbors_matches_predicting_row_by_row() {
+        let train = random_rows(29, 80);
+        let test = random_rows(30, 40);
+        let sequential: Vec<String> = test.iter()
+            .map(|row| predict_classification(&train, row, 5, DistanceMetric::Manhattan, Voting::InverseDistance).unwrap())
+            .collect();
+        assert_eq!(k_nearest_neighbors(&train, &test, 5, DistanceMetric::Manhattan, Voting::InverseDistance).unwrap(), sequential);
+    }
+
+    /// Two classes on a line: "a" at 0 and 1, "b" at 3 and 4.
+    fn line_classes() -> Vec<Sample> {
+        vec![sample(&[0.0], "a"), sample(&[1.0], "a"), sample(&[3.0], "b"), sample(&[4.0], "b")]
+    }
+
+    #[test]
+    fn confidence_is_the_winning_share_of_neighbors() {
+        let train = line_classes();
+        let euclidean = DistanceMetric::Euclidean;
+        assert_eq!(predict_with_confidence(&train, &sample(&[0.2], "a"), 2, euclidean).unwrap(), ("a".to_string(), 1.0));
+
+        let (class, confidence) = predict_with_confidence(&train, &sample(&[1.9], "a"), 3, euclidean).unwrap();
+        assert_eq!(class, "a");
+        assert_eq!(confidence, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn predict_or_abstain_needs_confidence_above_the_threshold() {
+        let train = line_classes();
+        let euclidean = DistanceMetric::Euclidean;
+        assert_eq!(predict_or_abstain(&train, &sample(&[0.2], "a"), 2, euclidean, 0.5).unwrap(), Some("a".to_string()));
+        // A 2-2 split is exactly at the threshold.
+        assert_eq!(predict_or_abstain(&train, &sample(&[2.0], "a"), 4, euclidean, 0.5).unwrap(), None);
+    }
+
+    #[test]
+    fn neighbors_within_radius_respects_the_radius() {
+        let train = line_classes();
+        let query = sample(&[0.5], "a");
+        let tight = neighbors_within_radius(&train, &query, 0.5, DistanceMetric::Euclidean);
+        assert_eq!(tight.iter().map(|n| n.class.as_str()).collect::<Vec<_>>(), ["a", "a"]);
+        assert!(neighbors_within_radius(&train, &query, 0.1, DistanceMetric::Euclidean).is_empty());
+
+        let everything = neighbors_within_radius(&train, &query, 100.0, DistanceMetric::Euclidean);
+        assert_eq!(everything.len(), train.len());
+        assert!(everything.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+    }
+
+    #[test]
+    fn saved_models_load_with_identical_predictions() {
+        let test = random_rows(41, 20);
+        let mut model = KnnClassifier::new(3, DistanceMetric::Manhattan, Voting::ClassBalanced).with_scaler(Scaler::new());
+        model.fit(random_rows(40, 60)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("rusty_neighbors_{}_model.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        model.save(path).unwrap();
+        let loaded = KnnClassifier::load(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.unwrap().predict_batch(&test).unwrap(), model.predict_batch(&test).unwrap());
+    }
+
+    #[test]
+    fn get_neighbors_with_uses_the_given_distance() {
+        let train = two_classes();
+        let query = sample(&[0.0, 0.0], "a");
+        let neighbors = get_neighbors_with(&train, &query, 3, |_, _| 7.0);
+        assert_eq!(neighbors.len(), 3);
+        assert!(neighbors.iter().all(|n| n.distance == 7.0));
+
+        // Distance by the second feature alone, farthest first via negation.
+        let by_second = get_neighbors_with(&train, &query, 1, |a, b| -(a.features[1] - b.features[1]).abs());
+        assert_eq!(by_second[0].class, "b");
+        assert_eq!(by_second[0].distance, -6.0);
+    }
+
+    #[test]
+    fn neighbors_with_nan_distances_sort_last() {
+        let mut neighbors = [measured(2.0, "b"), measured(f64::NAN, "a"), measured(0.5, "c"), measured(2.0, "a")];
+        neighbors.sort();
+        let order: Vec<&str> = neighbors.iter().map(|n| n.class.as_str()).collect();
+        assert_eq!(order, ["c", "a", "b", "a"]);
+        assert!(neighbors[3].distance.is_nan());
+        assert_eq!(measured(f64::NAN, "a"), measured(f64::NAN, "a"));
+    }
+
+    #[test]
+    fn explain_prediction_lists_k_rows_nearest_first() {
+        let train = random_rows(48, 30);
+        let query = sample(&[0.0, 1.0, 0.0], "a");
+        let (class, rows) = explain_prediction(&train, &query, 4, DistanceMetric::Euclidean).unwrap();
+
+        assert_eq!(class, predict_classification(&train, &query, 4, DistanceMetric::Euclidean, Voting::Majority).unwrap());
+        assert_eq!(rows.len(), 4);
+        assert!(rows.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        for (row, row_distance) in &rows {
+            assert!(train.contains(row));
+            assert_eq!(*row_distance, distance(DistanceMetric::Euclidean, &query, row));
+        }
+    }
+
+    #[test]
+    fn k_larger_than_the_training_set_does_not_panic() {
+        let train = &two_classes()[..3];
+        let query = sample(&[0.0, 0.0], "a");
+        assert_eq!(get_neighbors(train, &query, 10, DistanceMetric::Euclidean).len(), 3);
+        assert_eq!(neighbor_indices(train, &query, 10, DistanceMetric::Euclidean).len(), 3);
+        assert!(matches!(
+            predict_classification(train, &query, 10, DistanceMetric::Euclidean, Voting::Majority),
+            Err(KnnError::KTooLarge { k: 10, n_rows: 3 })
+        ));
+    }
+
+    #[test]
+    fn predictions_from_an_empty_training_set_fail_gracefully() {
+        let query = sample(&[0.0, 0.0], "a");
+        let euclidean = DistanceMetric::Euclidean;
+        assert!(matches!(predict_classification(&[], &query, 3, euclidean, Voting::Majority), Err(KnnError::EmptyTraining)));
+        assert!(matches!(predict_proba(&[], &query, 3, euclidean, Voting::Majority), Err(KnnError::EmptyTraining)));
+        assert!(matches!(predict_with_confidence(&[], &query, 3, euclidean), Err(KnnError::EmptyTraining)));
+        assert!(matches!(explain_prediction(&[], &query, 3, euclidean), Err(KnnError::EmptyTraining)));
+        assert!(vote(Vec::new(), Voting::Majority).is_none());
+    }
+
+    #[test]
+    fn predict_proba_sums_to_one_and_leads_with_the_prediction() {
+        let train = random_rows(52, 60);
+        for query in random_rows(53, 10) {
+            for voting in [Voting::Majority, Voting::InverseDistance, Voting::ClassBalanced] {
+                let proba = predict_proba(&train, &query, 7, DistanceMetric::Euclidean, voting).unwrap();
+                let total: f64 = proba.iter().map(|(_, p)| p).sum();
+                assert!((total - 1.0).abs() < 1e-9, "{}", total);
+                assert_eq!(proba[0].0, predict_classification(&train, &query, 7, DistanceMetric::Euclidean, voting).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn kernel_bandwidth_moves_between_majority_and_nearest_neighbor() {
+        let neighbors = vec![measured(0.1, "a"), measured(1.0, "b"), measured(1.2, "b")];
+        assert_eq!(vote(neighbors.clone(), Voting::Kernel { bandwidth: 100.0 }).unwrap(), "b");
+        assert_eq!(vote(neighbors.clone(), Voting::Kernel { bandwidth: 0.1 }).unwrap(), "a");
+        // Every weight underflows here, and the tie-break still picks the nearest.
+        assert_eq!(vote(neighbors, Voting::Kernel { bandwidth: 1e-3 }).unwrap(), "a");
+    }
+
+    #[test]
+    fn class_balanced_voting_lets_a_rare_neighbor_win() {
+        let mut train: Vec<Sample> = (0..10).map(|i| sample(&[1.0 + i as f64], "common")).collect();
+        train.push(sample(&[0.0], "rare"));
+        let query = sample(&[0.2], "rare");
+        let euclidean = DistanceMetric::Euclidean;
+
+        assert_eq!(predict_classification(&train, &query, 4, euclidean, Voting::Majority).unwrap(), "common");
+        assert_eq!(predict_classification(&train, &query, 4, euclidean, Voting::ClassBalanced).unwrap(), "rare");
+
+        let mut model = KnnClassifier::new(4, euclidean, Voting::ClassBalanced);
+        model.fit(train).unwrap();
+        assert_eq!(model.predict(&query).unwrap(), "rare");
+    }
+
+    #[test]
+    fn neighbor_indices_finds_the_row_placed_next_to_the_query() {
+        let mut train = random_rows(11, 30);
+        let query = sample(&[1.0, -2.0, 0.5], "a");
+        train[17] = sample(&[1.01, -2.0, 0.5], "b");
+        train[4] = sample(&[1.0, -2.05, 0.5], "c");
+
+        assert_eq!(neighbor_indices(&train, &query, 2, DistanceMetric::Euclidean), [17, 4]);
+        assert_eq!(neighbor_indices(&train, &query, 1, DistanceMetric::Manhattan), [17]);
+    }
+
+    #[test]
+    fn neighbors_iter_take_matches_get_neighbors() {
+        let train = random_rows(13, 40);
+        let query = sample(&[0.5, 1.5, -2.0], "a");
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Manhattan] {
+            let lazy: Vec<(f64, String)> = neighbors_iter(&train, &query, metric)
+                .take(3)
+                .map(|neighbor| (neighbor.distance, neighbor.class))
+                .collect();
+            let eager: Vec<(f64, String)> = get_neighbors(&train, &query, 3, metric)
+                .into_iter()
+                .map(|neighbor| (neighbor.distance, neighbor.class))
+                .collect();
+            assert_eq!(lazy, eager);
+        }
+        assert_eq!(neighbors_iter(&train, &query, DistanceMetric::Euclidean).len(), 40);
+    }
+
+    #[test]
+    fn decision_scores_are_the_neighbor_fractions() {
+        let mut train = line_classes();
+        train.push(sample(&[10.0], "c"));
+        let scores = decision_scores(&train, &sample(&[1.4], "a"), 3, DistanceMetric::Euclidean).unwrap();
+
+        assert_eq!(scores.len(), 3);
+        assert!((scores["a"] - 2.0 / 3.0).abs() < 1e-12, "{}", scores["a"]);
+        assert!((scores["b"] - 1.0 / 3.0).abs() < 1e-12, "{}", scores["b"]);
+        assert_eq!(scores["c"], 0.0);
+    }
+}
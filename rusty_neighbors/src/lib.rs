@@ -0,0 +1,16 @@
+//! An implementation of k-Nearest Neighbors.
+
+pub mod balltree;
+pub mod data;
+#[cfg(feature = "ndarray")]
+pub mod dense;
+pub mod distance;
+pub mod error;
+pub mod kdtree;
+pub mod knn;
+pub mod metrics;
+pub mod outlier;
+pub mod preprocessing;
+pub mod resampling;
+pub mod topk;
+pub mod validation;
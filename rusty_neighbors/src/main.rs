@@ -1,166 +1,76 @@
+use std::env;
 use std::error::Error;
-use csv;
-use serde::Deserialize;
-use rand::Rng;
-use rand::distributions::Alphanumeric;
-use std::collections::HashMap;
-
-#[derive(Debug, Clone, Deserialize)]
-struct Flower {
-    sepal_length: f64,
-    sepal_width: f64,
-    petal_length: f64,
-    petal_width: f64,
-    class: String,
-}
-
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
-struct MeasuredFlower {
-    distance: f64,
-    class: String,
+use rusty_neighbors::data::load_csv;
+use rusty_neighbors::distance::DistanceMetric;
+use rusty_neighbors::knn::{k_nearest_neighbors, predict_classification, Voting};
+use rusty_neighbors::validation::evaluate_algorithm;
+
+/// Used when no path is given on the command line. Anchored to the crate
+/// directory so the binary works from any working directory.
+const DEFAULT_DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../iris.csv");
+
+const USAGE: &str = "usage: rusty_neighbors [--data PATH] [--folds N] [--k N] [--metric euclidean|manhattan|chebyshev|cosine|canberra|braycurtis|correlation|hamming|minkowski:P]";
+
+struct Args {
+    data: String,
+    n_folds: usize,
+    num_neighbors: usize,
+    metric: DistanceMetric,
 }
 
-impl Flower {
-    fn rowify(&self) -> Vec<f64> {
-        vec!(self.sepal_length, self.sepal_width, self.petal_length, self.petal_width)
-    }
-    fn rand_flower() -> Flower {
-        let mut rng = rand::thread_rng();
-        let s: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(7)
-        .map(char::from)
-        .collect();
-        Flower {
-            sepal_length: rng.gen_range(0.1..7.0),
-            sepal_width: rng.gen_range(0.1..7.0),
-            petal_length: rng.gen_range(0.1..7.0),
-            petal_width: rng.gen_range(0.1..7.0),
-            class: String::from(s),
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, Box<dyn Error>> {
+    let mut parsed = Args {
+        data: DEFAULT_DATA.to_string(),
+        n_folds: 5,
+        num_neighbors: 5,
+        metric: DistanceMetric::Euclidean,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value (see --help)", flag));
+        match arg.as_str() {
+            "--data" => parsed.data = value("--data")?,
+            "--folds" => parsed.n_folds = value("--folds")?.parse().map_err(|e| format!("invalid --folds: {}", e))?,
+            "--k" => parsed.num_neighbors = value("--k")?.parse().map_err(|e| format!("invalid --k: {}", e))?,
+            "--metric" => parsed.metric = value("--metric")?.parse()?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {} (see --help)", flag).into()),
+            // A bare argument is still accepted as the dataset path.
+            path => parsed.data = path.to_string(),
         }
     }
-}
-
-fn predict_classification<'a>(train: &'a Vec<Flower>, test_row: &'a Flower, num_neighbors: usize) -> String {
-    let neighbors = get_neighbors(train, test_row, num_neighbors);
-    let mut m: HashMap<String, usize> = HashMap::new();
-    for x in neighbors {
-        *m.entry(x).or_default() += 1;
-    }
-    let max = m.into_iter().max_by_key(|(_, v)| *v).map(|(k, _)| k);
-
-    let max = max.unwrap();
-    // println!("Max thing{:?}", max.clone());
-    max
-}
-
-fn get_neighbors<'a>(train: &'a Vec<Flower>, test_row: &'a Flower, num_neighbors: usize) -> Vec<String> {
-    let mut distances = Vec::new();
-
-    for train_row in train {
-        let dist = euclidean_distance(test_row, train_row);
-        distances.push(MeasuredFlower {distance: dist, class: train_row.class.clone()});
-    }
-
-    distances.sort_by(|a, b| b.distance.partial_cmp(&a.distance).unwrap());
-    distances.reverse();
-    // println!("Distances and classes {:?}", distances.clone());
 
-    let mut neighbors = Vec::new();
-    for i in 0..num_neighbors {
-        let item = &distances[i];
-        neighbors.push(item.class.clone());
+    if parsed.num_neighbors < 1 {
+        return Err("--k must be at least 1".into());
     }
-    neighbors
-}
-
-fn euclidean_distance(row1: &Flower, row2: &Flower) -> f64 {
-    let mut distance = 0.0;
-
-    let measure1 = row1.rowify();
-    let measure2 = row2.rowify();
-    let mut measurements1 = measure1.iter();
-    let mut measurements2 = measure2.iter();
-
-    while let Some(measurement) = measurements1.next() {
-        distance += (measurement - measurements2.next().unwrap()).powi(2);
-    }
-
-    distance.sqrt()
-}
-
-fn read_from_file(path: &str) -> Result<Vec<Flower>, Box<dyn Error>> {
-    let mut reader = csv::Reader::from_path(path)?;
-
-    let headers = reader.headers()?;
-    println!("{:?}", headers);
-
-    let mut last = Vec::new();
-
-    for result in reader.deserialize() {
-        let record: Flower = result?;
-        last.push(record);
-        //println!("{:?}", record);
+    if parsed.n_folds < 2 {
+        return Err("--folds must be at least 2".into());
     }
-    Ok(last)
-}
-
-fn normalize_dataset() -> TODO {
-
-}
-
-fn accuracy_metric -> TODO {
-
-}
-
-fn cross_validation_split() -> TODO {
-
+    Ok(parsed)
 }
 
-fn evaluate_algorithm(dataset, algorithm, n_folds, num_neighbors) -> Vec<> {
-    
-}
-
-fn k_nearest_neighbors<'a>(train: &'a Vec<Flower>, test: &'a Vec<Flower>, num_neighbors: usize) -> Vec<String> {
-    let mut predictions = Vec::new();
-    for row in test {
-        let output = predict_classification(train, row, num_neighbors);
-        predictions.push(output);
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args(env::args().skip(1))?;
+    let path = args.data;
+    let dataset = load_csv(&path, true, b',')
+        .map_err(|e| format!("could not load dataset from {}: {}", path, e))?;
+
+    let n_folds = args.n_folds;
+    let num_neighbors = args.num_neighbors;
+    let metric = args.metric;
+    let voting = Voting::Majority;
+
+    let result = evaluate_algorithm(&dataset, k_nearest_neighbors, n_folds, num_neighbors, metric, voting, None)?;
+    println!("Scores: {:?}", result.scores);
+    println!("{}", result);
+
+    // Show one prediction: the first row, predicted from all the others.
+    if let Some((query, train)) = dataset.split_first() {
+        let prediction = predict_classification(train, query, num_neighbors, metric, voting)?;
+        println!("{} -> predicted {}", query, prediction);
     }
-    predictions
-
-}
-
-fn main() {
-
-    let dataset = read_from_file("../iris.csv").unwrap();
-
-    let n_folds = 5;
-    let num_neighbors = 5
-    
-    let scores = evaluate_algorithm(dataset, k_nearest_neighbors, n_folds, num_neighbors);
-    println!("Scores: {}" scores);
-    // println!("Mean Accuracy: {}", sum(scores)/len(scores);
-
-    //let mut dataset = Vec::new();
-    //for _x in 0..10 {
-    //    dataset.push(Flower::rand_flower());
-    //}
-    //for x in dataset.clone().iter() {
-    //    println!("{:?}\n", x);
-    //}
-
-    //let neighbors = get_neighbors(&dataset, &dataset[0], 3);
-    //let prediction = predict_classification(&dataset, &dataset[0], 3);
-    // println!("Expected {}, Got {}.", &dataset[0].class, prediction);
-    
-    // println!("The item to compare is: {:?}", &dataset[0]);
-
-    //for neighbor in neighbors {
-    //    println!("{:?}\n", neighbor);
-    //}
-
-    //if let Err(e) = read_from_file("../iris.csv") {
-    //    eprintln!("{}", e);
-    //}
+    Ok(())
 }
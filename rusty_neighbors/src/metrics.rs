@@ -0,0 +1,554 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Percentage of rows whose predicted class matches the actual one. NaN when
+/// both slices are empty, since there is nothing to score.
+///
+/// Panics unless `actual` and `predicted` have the same length.
+pub fn accuracy_metric(actual: &[String], predicted: &[String]) -> f64 {
+    assert_eq!(
+        actual.len(), predicted.len(),
+        "accuracy_metric needs one prediction per row, got {} actual and {} predicted",
+        actual.len(), predicted.len()
+    );
+    let mut correct = 0;
+    for (a, p) in actual.iter().zip(predicted.iter()) {
+        if a == p {
+            correct += 1;
+        }
+    }
+    correct as f64 / actual.len() as f64 * 100.0
+}
+
+/// Like `accuracy_metric`, but each row counts with its weight: the
+/// percentage of the total weight that sits on correctly classified rows.
+/// NaN when the weights sum to zero.
+///
+/// Panics unless the three slices have the same length.
+pub fn weighted_accuracy(actual: &[String], predicted: &[String], weights: &[f64]) -> f64 {
+    assert!(
+        actual.len() == predicted.len() && actual.len() == weights.len(),
+        "weighted_accuracy needs equal lengths, got {} actual, {} predicted and {} weights",
+        actual.len(), predicted.len(), weights.len()
+    );
+    let mut correct = 0.0;
+    for ((a, p), w) in actual.iter().zip(predicted).zip(weights) {
+        if a == p {
+            correct += w;
+        }
+    }
+    correct / weights.iter().sum::<f64>() * 100.0
+}
+
+/// Percentage of rows whose actual class is among the first `k` entries of
+/// its ranked candidates, as returned by `predict_ranked`. With `k = 1` this
+/// is `accuracy_metric` of the top candidates.
+///
+/// Panics unless `actual` and `ranked_predictions` have the same length.
+pub fn top_k_accuracy(actual: &[String], ranked_predictions: &[Vec<String>], k: usize) -> f64 {
+    assert_eq!(
+        actual.len(), ranked_predictions.len(),
+        "top_k_accuracy needs one ranking per row, got {} actual and {} rankings",
+        actual.len(), ranked_predictions.len()
+    );
+    let mut correct = 0;
+    for (a, ranked) in actual.iter().zip(ranked_predictions.iter()) {
+        if ranked.iter().take(k).any(|p| p == a) {
+            correct += 1;
+        }
+    }
+    correct as f64 / actual.len() as f64 * 100.0
+}
+
+/// Smallest probability `log_loss` will take the log of, so a true class
+/// given zero probability costs a large but finite penalty.
+pub const LOG_LOSS_EPSILON: f64 = 1e-15;
+
+/// Mean negative log-likelihood of the actual class under each row's
+/// predicted distribution, as returned by `predict_proba`. A class missing
+/// from a row's distribution counts as probability 0. Probabilities are
+/// clamped to `LOG_LOSS_EPSILON..=1.0` before taking the log.
+///
+/// Panics unless `actual` and `probas` have the same length.
+pub fn log_loss(actual: &[String], probas: &[Vec<(String, f64)>]) -> f64 {
+    assert_eq!(
+        actual.len(), probas.len(),
+        "log_loss needs one distribution per row, got {} actual and {} distributions",
+        actual.len(), probas.len()
+    );
+    let mut total = 0.0;
+    for (a, proba) in actual.iter().zip(probas.iter()) {
+        let p = proba.iter()
+            .find(|(class, _)| class == a)
+            .map_or(0.0, |&(_, p)| p);
+        total -= p.clamp(LOG_LOSS_EPSILON, 1.0).ln();
+    }
+    total / actual.len() as f64
+}
+
+/// Mean over rows of the squared distance between each row's predicted
+/// distribution, as returned by `predict_proba`, and the one-hot vector of
+/// its actual class. 0.0 is perfect and 2.0 the worst possible (all of the
+/// probability on one wrong class). A class missing from a row's
+/// distribution counts as probability 0. Unlike `log_loss` a confident
+/// mistake costs a bounded amount.
+///
+/// Panics unless `actual` and `probas` have the same length.
+pub fn brier_score(actual: &[String], probas: &[Vec<(String, f64)>]) -> f64 {
+    assert_eq!(
+        actual.len(), probas.len(),
+        "brier_score needs one distribution per row, got {} actual and {} distributions",
+        actual.len(), probas.len()
+    );
+    let mut total = 0.0;
+    for (a, proba) in actual.iter().zip(probas.iter()) {
+        let mut found = false;
+        for (class, p) in proba {
+            let target = if class == a { 1.0 } else { 0.0 };
+            found |= class == a;
+            total += (p - target).powi(2);
+        }
+        if !found {
+            total += 1.0;
+        }
+    }
+    total / actual.len() as f64
+}
+
+/// Every label that appears in either `actual` or `predicted`, sorted.
+pub fn class_labels(actual: &[String], predicted: &[String]) -> Vec<String> {
+    let labels: BTreeSet<&String> = actual.iter().chain(predicted.iter()).collect();
+    labels.into_iter().cloned().collect()
+}
+
+/// Number of actual rows in each class. Takes `predicted` too so classes that
+/// only ever get predicted are listed with support 0.
+pub fn class_support(actual: &[String], predicted: &[String]) -> HashMap<String, usize> {
+    let mut support: HashMap<String, usize> = class_labels(actual, predicted)
+        .into_iter()
+        .map(|class| (class, 0))
+        .collect();
+    for a in actual {
+        *support.entry(a.clone()).or_default() += 1;
+    }
+    support
+}
+
+/// Counts of `(actual, predicted)` label pairs. Pairs that never occur are
+/// absent rather than stored as zero, so look entries up with
+/// `get(..).unwrap_or(&0)`.
+pub fn confusion_matrix(actual: &[String], predicted: &[String]) -> HashMap<(String, String), usize> {
+    let mut matrix = HashMap::new();
+    for (a, p) in actual.iter().zip(predicted.iter()) {
+        *matrix.entry((a.clone(), p.clone())).or_default() += 1;
+    }
+    matrix
+}
+
+/// `numerator / denominator`, or 0.0 when the denominator is zero.
+fn safe_ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 { 0.0 } else { numerator / denominator }
+}
+
+/// Per-class `(precision, recall, f1)`. A class with no predicted positives
+/// gets precision 0.0 and one with no actual positives gets recall 0.0,
+/// rather than NaN.
+pub fn precision_recall_f1(actual: &[String], predicted: &[String]) -> HashMap<String, (f64, f64, f64)> {
+    let matrix = confusion_matrix(actual, predicted);
+    let mut scores = HashMap::new();
+
+    for class in class_labels(actual, predicted) {
+        let mut true_positives = 0;
+        let mut predicted_positives = 0;
+        let mut actual_positives = 0;
+        for ((a, p), count) in &matrix {
+            if *a == class && *p == class {
+                true_positives += count;
+            }
+            if *p == class {
+                predicted_positives += count;
+            }
+            if *a == class {
+                actual_positives += count;
+            }
+        }
+
+        let precision = safe_ratio(true_positives as f64, predicted_positives as f64);
+        let recall = safe_ratio(true_positives as f64, actual_positives as f64);
+        let f1 = safe_ratio(2.0 * precision * recall, precision + recall);
+        scores.insert(class, (precision, recall, f1));
+    }
+    scores
+}
+
+/// Unweighted mean of the per-class `(precision, recall, f1)` scores.
+pub fn macro_average(scores: &HashMap<String, (f64, f64, f64)>) -> (f64, f64, f64) {
+    let n = scores.len() as f64;
+    let (precision, recall, f1) = scores.values().fold((0.0, 0.0, 0.0), |acc, s| {
+        (acc.0 + s.0, acc.1 + s.1, acc.2 + s.2)
+    });
+    (safe_ratio(precision, n), safe_ratio(recall, n), safe_ratio(f1, n))
+}
+
+/// Mean of the per-class recalls, as a percentage like `accuracy_metric`, so
+/// every class counts equally however rare it is. Classes are the union of
+/// both label sets; one that only ever appears in `predicted` has no recall
+/// to average and is left out.
+pub fn balanced_accuracy(actual: &[String], predicted: &[String]) -> f64 {
+    let matrix = confusion_matrix(actual, predicted);
+    let mut recalls = Vec::new();
+
+    for class in class_labels(actual, predicted) {
+        let mut true_positives = 0;
+        let mut actual_positives = 0;
+        for ((a, p), count) in &matrix {
+            if *a == class {
+                actual_positives += count;
+                if *p == class {
+                    true_positives += count;
+                }
+            }
+        }
+        if actual_positives > 0 {
+            recalls.push(true_positives as f64 / actual_positives as f64);
+        }
+    }
+    recalls.iter().sum::<f64>() / recalls.len() as f64 * 100.0
+}
+
+/// Cohen's kappa: agreement between `actual` and `predicted` corrected for the
+/// agreement expected by chance, in -1..1. Returns 0.0 when chance agreement
+/// is already total, i.e. both sides use one and the same label throughout.
+pub fn cohens_kappa(actual: &[String], predicted: &[String]) -> f64 {
+    let n = actual.len().min(predicted.len()) as f64;
+    let matrix = confusion_matrix(actual, predicted);
+
+    let mut observed = 0.0;
+    let mut chance = 0.0;
+    for class in class_labels(actual, predicted) {
+        let mut actual_count = 0;
+        let mut predicted_count = 0;
+        for ((a, p), count) in &matrix {
+            if *a == class && *p == class {
+                observed += *count as f64;
+            }
+            if *a == class {
+                actual_count += count;
+            }
+            if *p == class {
+                predicted_count += count;
+            }
+        }
+        chance += (actual_count as f64 / n) * (predicted_count as f64 / n);
+    }
+
+    safe_ratio(observed / n - chance, 1.0 - chance)
+}
+
+/// Matthews correlation coefficient for a binary problem, treating `positive`
+/// as the positive class and every other label as negative. Ranges from -1
+/// (always wrong) to 1 (always right); returns 0.0 when any row or column of
+/// the 2x2 table is empty.
+pub fn matthews_corrcoef(actual: &[String], predicted: &[String], positive: &str) -> f64 {
+    let (mut tp, mut tn, mut fp, mut fn_) = (0.0, 0.0, 0.0, 0.0);
+    for (a, p) in actual.iter().zip(predicted.iter()) {
+        match (a == positive, p == positive) {
+            (true, true) => tp += 1.0,
+            (false, false) => tn += 1.0,
+            (false, true) => fp += 1.0,
+            (true, false) => fn_ += 1.0,
+        }
+    }
+    let denominator: f64 = (tp + fp) * (tp + fn_) * (tn + fp) * (tn + fn_);
+    safe_ratio(tp * tn - fp * fn_, denominator.sqrt())
+}
+
+/// A per-class precision/recall/F1 table with support (the number of actual
+/// rows in each class), followed by overall accuracy and the macro and
+/// support-weighted averages, laid out like scikit-learn's report:
+///
+/// ```text
+///                  precision    recall  f1-score   support
+///
+///     Iris-setosa       1.00      1.00      1.00        50
+///          ...
+///        accuracy                           0.96       150
+///       macro avg       0.96      0.96      0.96       150
+///    weighted avg       0.96      0.96      0.96       150
+/// ```
+pub fn classification_report(actual: &[String], predicted: &[String]) -> String {
+    let scores = precision_recall_f1(actual, predicted);
+    let labels = class_labels(actual, predicted);
+    let support = class_support(actual, predicted);
+    let total = actual.len();
+
+    let width = labels.iter().map(|l| l.len()).chain(["weighted avg".len()]).max().unwrap_or(0);
+    let mut report = format!("{:>width$} {:>10} {:>9} {:>9} {:>9}\n\n", "", "precision", "recall", "f1-score", "support");
+
+    let mut weighted = (0.0, 0.0, 0.0);
+    for label in &labels {
+        let (precision, recall, f1) = scores[label];
+        let count = support[label];
+        let w = safe_ratio(count as f64, total as f64);
+        weighted = (weighted.0 + w * precision, weighted.1 + w * recall, weighted.2 + w * f1);
+        report += &format!("{:>width$} {:>10.2} {:>9.2} {:>9.2} {:>9}\n", label, precision, recall, f1, count);
+    }
+
+    let accuracy = safe_ratio(accuracy_metric(actual, predicted), 100.0);
+    let (precision, recall, f1) = macro_average(&scores);
+    report += &format!("\n{:>width$} {:>10} {:>9} {:>9.2} {:>9}\n", "accuracy", "", "", accuracy, total);
+    report += &format!("{:>width$} {:>10.2} {:>9.2} {:>9.2} {:>9}\n", "macro avg", precision, recall, f1, total);
+    report += &format!("{:>width$} {:>10.2} {:>9.2} {:>9.2} {:>9}\n", "weighted avg", weighted.0, weighted.1, weighted.2, total);
+    report
+}
+
+/// ROC curve points `(false positive rate, true positive rate)` for a binary
+/// task, from `(0, 0)` to `(1, 1)`. `scores` holds each row's score for the
+/// `positive` class, e.g. its entry from `decision_scores`; the threshold is
+/// swept from the highest score down, with one point per distinct score so
+/// tied rows move the curve together. A rate whose class has no rows is 0.0.
+///
+/// Panics unless `actual` and `scores` have the same length.
+pub fn roc_points(actual: &[String], scores: &[f64], positive: &str) -> Vec<(f64, f64)> {
+    assert_eq!(actual.len(), scores.len(), "roc_points needs one score per row");
+    let mut rows: Vec<(f64, bool)> = scores.iter()
+        .zip(actual)
+        .map(|(&score, class)| (score, class == positive))
+        .collect();
+    rows.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let positives = rows.iter().filter(|(_, is_positive)| *is_positive).count() as f64;
+    let negatives = rows.len() as f64 - positives;
+
+    let mut points = vec![(0.0, 0.0)];
+    let (mut true_positives, mut false_positives) = (0.0, 0.0);
+    for (i, &(score, is_positive)) in rows.iter().enumerate() {
+        if is_positive {
+            true_positives += 1.0;
+        } else {
+            false_positives += 1.0;
+        }
+        if rows.get(i + 1).map(|next| next.0) != Some(score) {
+            points.push((safe_ratio(false_positives, negatives), safe_ratio(true_positives, positives)));
+        }
+    }
+    points
+}
+
+/// Area under the `roc_points` curve by the trapezoidal rule: 1.0 when every
+/// positive row scores above every negative one, about 0.5 for scores that
+/// carry no information. NaN when `actual` doesn't contain both positive and
+/// negative rows, since one of the rates is then undefined.
+///
+/// Panics unless `actual` and `scores` have the same length.
+pub fn roc_auc(actual: &[String], scores: &[f64], positive: &str) -> f64 {
+    let positives = actual.iter().filter(|class| *class == positive).count();
+    if positives == 0 || positives == actual.len() {
+        return f64::NAN;
+    }
+    roc_points(actual, scores, positive)
+        .windows(2)
+        .map(|pair| (pair[1].0 - pair[0].0) * (pair[0].1 + pair[1].1) / 2.0)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn top_k_accuracy_counts_a_label_ranked_second() {
+        let actual = labels(&["a", "b"]);
+        let ranked = vec![labels(&["b", "a", "c"]), labels(&["b", "c"])];
+        assert_eq!(top_k_accuracy(&actual, &ranked, 1), 50.0);
+        assert_eq!(top_k_accuracy(&actual, &ranked, 2), 100.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "one ranking per row")]
+    fn top_k_accuracy_rejects_mismatched_lengths() {
+        top_k_accuracy(&labels(&["a", "b"]), &[labels(&["a"])], 1);
+    }
+
+    fn pair(a: &str, p: &str) -> (String, String) {
+        (a.to_string(), p.to_string())
+    }
+
+    #[test]
+    fn confusion_matrix_counts_three_classes() {
+        let actual = labels(&["a", "a", "a", "b", "b", "c", "c", "c"]);
+        let predicted = labels(&["a", "a", "b", "b", "c", "c", "c", "a"]);
+        let matrix = confusion_matrix(&actual, &predicted);
+
+        assert_eq!(matrix[&pair("a", "a")], 2);
+        assert_eq!(matrix[&pair("b", "b")], 1);
+        assert_eq!(matrix[&pair("c", "c")], 2);
+        assert_eq!(matrix[&pair("a", "b")], 1);
+        assert_eq!(matrix[&pair("b", "c")], 1);
+        assert_eq!(matrix[&pair("c", "a")], 1);
+        assert_eq!(matrix.get(&pair("b", "a")), None);
+        assert_eq!(matrix.values().sum::<usize>(), actual.len());
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn precision_recall_and_f1_on_imbalanced_classes() {
+        let mut actual = labels(&["neg"; 8]);
+        actual.extend(labels(&["pos", "pos"]));
+        let mut predicted = labels(&["neg"; 7]);
+        predicted.extend(labels(&["pos", "pos", "neg"]));
+        let scores = precision_recall_f1(&actual, &predicted);
+
+        assert_eq!(scores["pos"], (0.5, 0.5, 0.5));
+        let (precision, recall, f1) = scores["neg"];
+        assert_close(precision, 7.0 / 8.0);
+        assert_close(recall, 7.0 / 8.0);
+        assert_close(f1, 7.0 / 8.0);
+
+        let (precision, recall, f1) = macro_average(&scores);
+        assert_close(precision, 0.6875);
+        assert_close(recall, 0.6875);
+        assert_close(f1, 0.6875);
+    }
+
+    #[test]
+    fn cohens_kappa_matches_a_hand_computed_value() {
+        // Observed agreement 0.8, chance agreement 0.5 * 0.5 + 0.5 * 0.5 = 0.5,
+        // so kappa = (0.8 - 0.5) / (1 - 0.5) = 0.6.
+        let actual = labels(&["a", "a", "a", "a", "a", "b", "b", "b", "b", "b"]);
+        let predicted = labels(&["a", "a", "a", "a", "b", "a", "b", "b", "b", "b"]);
+        assert_close(cohens_kappa(&actual, &predicted), 0.6);
+        assert_close(cohens_kappa(&actual, &actual), 1.0);
+    }
+
+    #[test]
+    fn matthews_corrcoef_is_one_when_perfect_and_minus_one_when_inverted() {
+        let actual = labels(&["pos", "pos", "neg", "neg", "neg"]);
+        let inverted = labels(&["neg", "neg", "pos", "pos", "pos"]);
+        assert_close(matthews_corrcoef(&actual, &actual, "pos"), 1.0);
+        assert_close(matthews_corrcoef(&actual, &inverted, "pos"), -1.0);
+    }
+
+    fn proba(entries: &[(&str, f64)]) -> Vec<(String, f64)> {
+        entries.iter().map(|&(class, p)| (class.to_string(), p)).collect()
+    }
+
+    #[test]
+    fn log_loss_punishes_confident_mistakes() {
+        let actual = labels(&["a"]);
+        let confident_right = log_loss(&actual, &[proba(&[("a", 0.9), ("b", 0.1)])]);
+        let confident_wrong = log_loss(&actual, &[proba(&[("b", 0.9), ("a", 0.1)])]);
+        assert_close(confident_right, -(0.9_f64.ln()));
+        assert_close(confident_wrong, -(0.1_f64.ln()));
+        assert!(confident_wrong > confident_right);
+
+        // A true class given no probability costs a finite penalty.
+        assert_close(log_loss(&actual, &[proba(&[("b", 1.0)])]), -LOG_LOSS_EPSILON.ln());
+    }
+
+    #[test]
+    #[should_panic(expected = "one distribution per row")]
+    fn log_loss_rejects_mismatched_lengths() {
+        log_loss(&labels(&["a", "b"]), &[proba(&[("a", 1.0)])]);
+    }
+
+    #[test]
+    fn balanced_accuracy_sees_through_a_majority_only_classifier() {
+        let mut actual = labels(&["common"; 95]);
+        actual.extend(labels(&["rare"; 5]));
+        let predicted = labels(&["common"; 100]);
+        assert_eq!(accuracy_metric(&actual, &predicted), 95.0);
+        assert_close(balanced_accuracy(&actual, &predicted), 50.0);
+        assert_close(balanced_accuracy(&actual, &actual), 100.0);
+    }
+
+    #[test]
+    fn classification_report_lists_every_class_and_the_accuracy() {
+        let actual = labels(&["setosa", "setosa", "virginica", "versicolor"]);
+        let predicted = labels(&["setosa", "virginica", "virginica", "versicolor"]);
+        let report = classification_report(&actual, &predicted);
+
+        for class in ["setosa", "versicolor", "virginica", "macro avg", "weighted avg"] {
+            assert!(report.lines().any(|line| line.trim_start().starts_with(class)), "no {} line in\n{}", class, report);
+        }
+        let accuracy = report.lines().find(|line| line.trim_start().starts_with("accuracy")).unwrap();
+        assert_eq!(accuracy.split_whitespace().collect::<Vec<_>>(), ["accuracy", "0.75", "4"]);
+    }
+
+    #[test]
+    fn weighted_accuracy_favors_heavy_correct_rows() {
+        let actual = labels(&["a", "b", "b", "b"]);
+        let predicted = labels(&["a", "a", "a", "a"]);
+        assert_eq!(accuracy_metric(&actual, &predicted), 25.0);
+        assert_eq!(weighted_accuracy(&actual, &predicted, &[9.0, 1.0, 1.0, 1.0]), 75.0);
+        assert_eq!(weighted_accuracy(&actual, &predicted, &[1.0; 4]), 25.0);
+    }
+
+    #[test]
+    fn class_support_counts_actual_rows_per_class() {
+        let actual = labels(&["a", "a", "b", "c", "c", "c"]);
+        let predicted = labels(&["a", "d", "b", "c", "a", "c"]);
+        let support = class_support(&actual, &predicted);
+        assert_eq!(support.len(), 4);
+        assert_eq!((support["a"], support["b"], support["c"]), (2, 1, 3));
+        // Only ever predicted.
+        assert_eq!(support["d"], 0);
+    }
+
+    #[test]
+    fn accuracy_metric_is_nan_with_nothing_to_score() {
+        assert!(accuracy_metric(&[], &[]).is_nan());
+        assert_eq!(accuracy_metric(&labels(&["a", "b", "b", "c"]), &labels(&["a", "b", "c", "c"])), 75.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "one prediction per row")]
+    fn accuracy_metric_rejects_mismatched_lengths() {
+        accuracy_metric(&labels(&["a", "b"]), &labels(&["a"]));
+    }
+
+    #[test]
+    fn brier_score_penalizes_an_overconfident_mistake() {
+        let actual = labels(&["a"]);
+        let calibrated = brier_score(&actual, &[proba(&[("a", 0.8), ("b", 0.2)])]);
+        let overconfident = brier_score(&actual, &[proba(&[("b", 1.0)])]);
+        // (0.8 - 1)^2 + 0.2^2, and 1^2 for b plus 1 for the missing a.
+        assert_close(calibrated, 0.08);
+        assert_close(overconfident, 2.0);
+        assert!(calibrated < overconfident);
+        assert_eq!(brier_score(&actual, &[proba(&[("a", 1.0), ("b", 0.0)])]), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "one distribution per row")]
+    fn brier_score_rejects_mismatched_lengths() {
+        brier_score(&labels(&["a"]), &[proba(&[("a", 1.0)]), proba(&[("b", 1.0)])]);
+    }
+
+    #[test]
+    fn roc_points_reach_the_top_left_corner_on_separable_scores() {
+        let actual = labels(&["pos", "neg", "pos", "neg", "pos"]);
+        let scores = [0.9, 0.2, 0.7, 0.4, 0.8];
+        let points = roc_points(&actual, &scores, "pos");
+
+        assert_eq!(points.first(), Some(&(0.0, 0.0)));
+        assert_eq!(points.last(), Some(&(1.0, 1.0)));
+        assert!(points.contains(&(0.0, 1.0)), "{:?}", points);
+        assert!(points.windows(2).all(|pair| pair[0].0 <= pair[1].0 && pair[0].1 <= pair[1].1));
+    }
+
+    #[test]
+    fn roc_auc_is_one_on_separable_scores() {
+        let actual = labels(&["pos", "neg", "pos", "neg", "pos", "neg"]);
+        let scores = [0.9, 0.2, 0.7, 0.4, 0.8, 0.5];
+        assert_close(roc_auc(&actual, &scores, "pos"), 1.0);
+        // Ranking every negative above every positive flips it.
+        assert_close(roc_auc(&actual, &scores, "neg"), 0.0);
+        assert!(roc_auc(&labels(&["pos", "pos"]), &[0.1, 0.9], "pos").is_nan());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::data::Sample;
+use crate::distance::{distance, DistanceMetric};
+use crate::knn::{select_nearest, MeasuredFlower};
+
+/// The `num_neighbors` rows of `dataset` nearest to row `i`, leaving out row
+/// `i` itself, nearest first. Each neighbor's `class` is its row index.
+fn nearest_others<L>(dataset: &[Sample<L>], i: usize, num_neighbors: usize, metric: DistanceMetric) -> Vec<MeasuredFlower<usize>> {
+    let others = dataset.iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .map(|(j, row)| MeasuredFlower { distance: distance(metric, &dataset[i], row), class: j })
+        .collect();
+    select_nearest(others, num_neighbors)
+}
+
+/// Each row's mean distance to its `num_neighbors` nearest other rows. Rows
+/// far from everything else score highest; compare with `is_outlier`.
+///
+/// A row with nothing to average over, because `num_neighbors` is 0 or it is
+/// the only row, scores NaN, which `is_outlier` never flags.
+pub fn outlier_scores<L>(dataset: &[Sample<L>], num_neighbors: usize, metric: DistanceMetric) -> Vec<f64> {
+    (0..dataset.len())
+        .map(|i| {
+            let neighbors = nearest_others(dataset, i, num_neighbors, metric);
+            neighbors.iter().map(|n| n.distance).sum::<f64>() / neighbors.len() as f64
+        })
+        .collect()
+}
+
+/// Whether a score from `outlier_scores` is above `threshold`.
+pub fn is_outlier(score: f64, threshold: f64) -> bool {
+    score > threshold
+}
+
+/// Added to mean reachability distances so rows with `num_neighbors` exact
+/// duplicates get a large, finite density instead of infinity.
+const DENSITY_EPSILON: f64 = 1e-10;
+
+/// Local outlier factor of each row: the mean local reachability density of
+/// its `num_neighbors` nearest other rows divided by its own. Scores near 1.0
+/// mean the row is about as dense as its neighborhood; scores well above 1.0
+/// mark outliers. Unlike `outlier_scores` this adapts to clusters of
+/// different densities.
+pub fn local_outlier_factor<L>(dataset: &[Sample<L>], num_neighbors: usize, metric: DistanceMetric) -> Vec<f64> {
+    let neighborhoods: Vec<Vec<MeasuredFlower<usize>>> = (0..dataset.len())
+        .map(|i| nearest_others(dataset, i, num_neighbors, metric))
+        .collect();
+    // Distance to each row's k-th nearest neighbor.
+    let k_distances: Vec<f64> = neighborhoods.iter()
+        .map(|neighbors| neighbors.last().map_or(0.0, |n| n.distance))
+        .collect();
+
+    // Local reachability density: the inverse of the mean reachability
+    // distance max(k_distance(o), d(p, o)) to each neighbor o.
+    let densities: Vec<f64> = neighborhoods.iter()
+        .map(|neighbors| {
+            let reach = neighbors.iter()
+                .map(|n| n.distance.max(k_distances[n.class]))
+                .sum::<f64>() / neighbors.len() as f64;
+            1.0 / (reach + DENSITY_EPSILON)
+        })
+        .collect();
+
+    neighborhoods.iter()
+        .zip(&densities)
+        .map(|(neighbors, density)| {
+            let neighbor_density = neighbors.iter().map(|n| densities[n.class]).sum::<f64>() / neighbors.len() as f64;
+            neighbor_density / density
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two tight clusters, plus one row far from both as the last row.
+    fn clusters_and_outlier() -> Vec<Sample> {
+        let mut dataset = Vec::new();
+        for i in 0..5 {
+            let offset = i as f64 * 0.1;
+            dataset.push(Sample::new(vec![offset, offset], "a"));
+            dataset.push(Sample::new(vec![10.0 + offset, 10.0 - offset], "b"));
+        }
+        dataset.push(Sample::new(vec![30.0, -20.0], "a"));
+        dataset
+    }
+
+    fn highest(scores: &[f64]) -> usize {
+        (0..scores.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b])).unwrap()
+    }
+
+    #[test]
+    fn a_far_point_has_the_highest_outlier_score() {
+        let dataset = clusters_and_outlier();
+        let scores = outlier_scores(&dataset, 3, DistanceMetric::Euclidean);
+        assert_eq!(highest(&scores), dataset.len() - 1);
+        assert!(is_outlier(scores[dataset.len() - 1], 5.0));
+        assert!(!is_outlier(scores[0], 5.0));
+    }
+
+    #[test]
+    fn outlier_scores_are_nan_without_neighbors() {
+        let dataset = clusters_and_outlier();
+        assert!(outlier_scores(&dataset, 0, DistanceMetric::Euclidean).iter().all(|score| score.is_nan()));
+        assert!(outlier_scores(&dataset[..1], 3, DistanceMetric::Euclidean)[0].is_nan());
+        assert!(!is_outlier(f64::NAN, 0.0));
+    }
+
+    #[test]
+    fn local_outlier_factor_flags_an_isolated_point() {
+        let dataset = clusters_and_outlier();
+        let factors = local_outlier_factor(&dataset, 3, DistanceMetric::Euclidean);
+        let outlier = factors[dataset.len() - 1];
+        assert_eq!(highest(&factors), dataset.len() - 1);
+        assert!(outlier > 5.0, "{}", outlier);
+        assert!(factors[..dataset.len() - 1].iter().all(|&factor| factor < 2.0), "{:?}", factors);
+    }
+}
@@ -0,0 +1,602 @@
+use serde::{Deserialize, Serialize};
+use crate::data::Sample;
+use crate::distance::covariance_matrix;
+use crate::knn::get_neighbors_with;
+
+/// Returns one `(min, max)` tuple per feature column.
+pub fn dataset_minmax(dataset: &[Sample]) -> Vec<(f64, f64)> {
+    let mut minmax = Vec::new();
+    let Some(first) = dataset.first() else {
+        return minmax;
+    };
+
+    for i in 0..first.features.len() {
+        let mut min = first.features[i];
+        let mut max = first.features[i];
+        for row in dataset {
+            min = min.min(row.features[i]);
+            max = max.max(row.features[i]);
+        }
+        minmax.push((min, max));
+    }
+    minmax
+}
+
+/// Rescales every column to the 0..1 range using the ranges from `dataset_minmax`.
+/// Columns with a zero range (every row identical) are mapped to 0.0.
+pub fn normalize_dataset(dataset: &mut [Sample], minmax: &[(f64, f64)]) {
+    for row in dataset.iter_mut() {
+        for (i, value) in row.features.iter_mut().enumerate() {
+            let range = minmax[i].1 - minmax[i].0;
+            *value = if range == 0.0 { 0.0 } else { (*value - minmax[i].0) / range };
+        }
+    }
+}
+
+/// Returns one `(mean, standard deviation)` tuple per feature column, using
+/// the population standard deviation.
+pub fn dataset_mean_std(dataset: &[Sample]) -> Vec<(f64, f64)> {
+    let mut stats = Vec::new();
+    let Some(first) = dataset.first() else {
+        return stats;
+    };
+    let n = dataset.len() as f64;
+
+    for i in 0..first.features.len() {
+        let mean = dataset.iter().map(|row| row.features[i]).sum::<f64>() / n;
+        let variance = dataset.iter().map(|row| (row.features[i] - mean).powi(2)).sum::<f64>() / n;
+        stats.push((mean, variance.sqrt()));
+    }
+    stats
+}
+
+/// Centers every column on 0 with unit variance using the stats from
+/// `dataset_mean_std`. Columns with zero standard deviation are mapped to 0.0.
+pub fn standardize_dataset(dataset: &mut [Sample], stats: &[(f64, f64)]) {
+    for row in dataset.iter_mut() {
+        for (i, value) in row.features.iter_mut().enumerate() {
+            let (mean, std) = stats[i];
+            *value = if std == 0.0 { 0.0 } else { (*value - mean) / std };
+        }
+    }
+}
+
+/// The `q` quantile of sorted `values`, interpolating linearly between the
+/// two nearest ranks.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Returns one `(median, interquartile range)` tuple per feature column.
+pub fn dataset_median_iqr(dataset: &[Sample]) -> Vec<(f64, f64)> {
+    let n_features = dataset.first().map_or(0, |row| row.features.len());
+    (0..n_features)
+        .map(|i| {
+            let mut column: Vec<f64> = dataset.iter().map(|row| row.features[i]).collect();
+            column.sort_by(f64::total_cmp);
+            (quantile(&column, 0.5), quantile(&column, 0.75) - quantile(&column, 0.25))
+        })
+        .collect()
+}
+
+/// Centers every column on its median and divides by its interquartile range
+/// using the stats from `dataset_median_iqr`. Unlike `standardize_dataset` a
+/// few extreme values barely move the scale. Columns with a zero IQR are left
+/// unchanged.
+pub fn robust_scale(dataset: &mut [Sample], stats: &[(f64, f64)]) {
+    for row in dataset.iter_mut() {
+        for (value, &(median, iqr)) in row.features.iter_mut().zip(stats) {
+            if iqr != 0.0 {
+                *value = (*value - median) / iqr;
+            }
+        }
+    }
+}
+
+/// Returns each feature column's largest absolute value.
+pub fn dataset_maxabs(dataset: &[Sample]) -> Vec<f64> {
+    let n_features = dataset.first().map_or(0, |row| row.features.len());
+    (0..n_features)
+        .map(|i| dataset.iter().map(|row| row.features[i].abs()).fold(0.0, f64::max))
+        .collect()
+}
+
+/// Divides every column by its value from `dataset_maxabs`, so values land in
+/// -1..1 without being shifted and zeros stay zero. All-zero columns are left
+/// unchanged.
+pub fn maxabs_scale(dataset: &mut [Sample], maxabs: &[f64]) {
+    for row in dataset.iter_mut() {
+        for (value, &max) in row.features.iter_mut().zip(maxabs) {
+            if max != 0.0 {
+                *value /= max;
+            }
+        }
+    }
+}
+
+/// Learns a `Scaling::Standard` scaler one row at a time, for data that
+/// arrives in chunks. Welford's algorithm keeps a running mean and sum of
+/// squared deviations per column, so the result matches `dataset_mean_std`
+/// on the same rows without holding them all.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalScaler {
+    count: usize,
+    means: Vec<f64>,
+    /// Sum of squared deviations from the running mean, per column.
+    squares: Vec<f64>,
+}
+
+impl IncrementalScaler {
+    pub fn new() -> IncrementalScaler {
+        IncrementalScaler::default()
+    }
+
+    /// Folds `row` into the running stats. The first row fixes the number of
+    /// columns.
+    ///
+    /// Panics if a later row has a different number of features.
+    pub fn update(&mut self, row: &Sample) {
+        if self.count == 0 {
+            self.means = vec![0.0; row.features.len()];
+            self.squares = vec![0.0; row.features.len()];
+        }
+        assert_eq!(row.features.len(), self.means.len(), "every row needs the same number of features");
+        self.count += 1;
+        let n = self.count as f64;
+        for ((mean, square), &value) in self.means.iter_mut().zip(self.squares.iter_mut()).zip(&row.features) {
+            let delta = value - *mean;
+            *mean += delta / n;
+            *square += delta * (value - *mean);
+        }
+    }
+
+    /// Rows seen so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The `(mean, std)` of each column so far, using the population
+    /// standard deviation.
+    pub fn stats(&self) -> Vec<(f64, f64)> {
+        let n = self.count as f64;
+        self.means.iter()
+            .zip(&self.squares)
+            .map(|(&mean, &square)| (mean, (square / n).sqrt()))
+            .collect()
+    }
+
+    /// A standardizing `Scaler` with the stats seen so far.
+    pub fn finish(&self) -> Scaler {
+        Scaler {
+            scaling: Scaling::Standard,
+            params: self.stats(),
+        }
+    }
+}
+
+/// Maps class labels to `usize` ids and back, for numeric pipelines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LabelEncoder {
+    /// Distinct labels in sorted order; a label's id is its index.
+    classes: Vec<String>,
+}
+
+/// Learns the distinct classes in `dataset`. Ids follow sorted label order,
+/// so the same set of classes always gets the same ids.
+pub fn fit_labels(dataset: &[Sample]) -> LabelEncoder {
+    let mut classes: Vec<String> = dataset.iter().map(|row| row.class.clone()).collect();
+    classes.sort();
+    classes.dedup();
+    LabelEncoder { classes }
+}
+
+impl LabelEncoder {
+    /// The id of `class`.
+    ///
+    /// Panics if `class` was not in the fitted dataset.
+    pub fn encode(&self, class: &str) -> usize {
+        self.classes
+            .binary_search_by(|known| known.as_str().cmp(class))
+            .unwrap_or_else(|_| panic!("unknown class {:?}", class))
+    }
+
+    /// The label with id `id`.
+    ///
+    /// Panics if `id` is not below `len()`.
+    pub fn decode(&self, id: usize) -> &str {
+        &self.classes[id]
+    }
+
+    /// The known labels, indexed by id.
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+/// Power iterations allowed per principal component.
+const PCA_MAX_ITERATIONS: usize = 1000;
+
+/// Principal components learned by `pca_fit`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcaModel {
+    /// Per-column means, subtracted before projecting.
+    pub means: Vec<f64>,
+    /// Unit-length component directions, largest variance first.
+    pub components: Vec<Vec<f64>>,
+    /// The variance along each component.
+    pub variances: Vec<f64>,
+}
+
+/// Finds the `n_components` directions of greatest variance in `dataset`
+/// (at most one per feature), by power iteration on the covariance matrix,
+/// removing each component before finding the next.
+pub fn pca_fit(dataset: &[Sample], n_components: usize) -> PcaModel {
+    let n_features = dataset.first().map_or(0, |row| row.features.len());
+    let n = dataset.len() as f64;
+    let means = (0..n_features)
+        .map(|i| dataset.iter().map(|row| row.features[i]).sum::<f64>() / n)
+        .collect();
+    let mut covariance = covariance_matrix(dataset);
+
+    let mut components = Vec::new();
+    let mut variances = Vec::new();
+    for _ in 0..n_components.min(n_features) {
+        // Uneven starting weights, so the start is unlikely to be orthogonal
+        // to the component being looked for.
+        let mut vector: Vec<f64> = (1..=n_features).map(|i| i as f64).collect();
+        let mut variance = 0.0;
+        for _ in 0..PCA_MAX_ITERATIONS {
+            let product: Vec<f64> = covariance.iter()
+                .map(|row| row.iter().zip(&vector).map(|(c, v)| c * v).sum())
+                .collect();
+            let norm = product.iter().map(|x| x * x).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                break;
+            }
+            let next: Vec<f64> = product.iter().map(|x| x / norm).collect();
+            let change = next.iter().zip(&vector).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+            vector = next;
+            variance = norm;
+            if change < 1e-12 {
+                break;
+            }
+        }
+        // Normalize the sign so the largest weight is positive.
+        let largest = vector.iter().copied().fold(0.0, |a: f64, b| if b.abs() > a.abs() { b } else { a });
+        if largest < 0.0 {
+            vector.iter_mut().for_each(|v| *v = -*v);
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+        vector.iter_mut().for_each(|v| *v /= norm);
+
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value -= variance * vector[i] * vector[j];
+            }
+        }
+        components.push(vector);
+        variances.push(variance);
+    }
+    PcaModel { means, components, variances }
+}
+
+/// Replaces every row's features with its coordinates along `model`'s
+/// components.
+pub fn pca_transform(dataset: &mut [Sample], model: &PcaModel) {
+    for row in dataset.iter_mut() {
+        row.features = model.components.iter()
+            .map(|component| {
+                component.iter()
+                    .zip(&row.features)
+                    .zip(&model.means)
+                    .map(|((weight, value), mean)| weight * (value - mean))
+                    .sum()
+            })
+            .collect();
+    }
+}
+
+/// Euclidean distance over the features that are present (not NaN) in both
+/// rows.
+fn present_feature_distance(a: &Sample<f64>, b: &Sample<f64>) -> f64 {
+    a.features.iter()
+        .zip(&b.features)
+        .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Fills missing (NaN) features from the `k` nearest rows that have no
+/// missing values at all, averaging their value for that column. Distances
+/// ignore the column being filled and any other feature the row is missing.
+/// Donors are taken from the dataset as passed in, so values imputed for one
+/// column don't feed into another. Cells stay NaN if there are no complete
+/// rows to draw from.
+pub fn knn_impute(dataset: &mut [Sample], k: usize) {
+    let complete: Vec<&Sample> = dataset.iter()
+        .filter(|row| row.features.iter().all(|v| !v.is_nan()))
+        .collect();
+    let n_features = dataset.first().map_or(0, |row| row.features.len());
+
+    // column -> rows to fill -> imputed value
+    let mut fills = Vec::new();
+    for column in 0..n_features {
+        // Label each donor with its value in this column, and hide the column
+        // from the distance.
+        let donors: Vec<Sample<f64>> = complete.iter()
+            .map(|row| {
+                let mut features = row.features.clone();
+                features[column] = f64::NAN;
+                Sample { features, class: row.features[column] }
+            })
+            .collect();
+
+        for (i, row) in dataset.iter().enumerate() {
+            if !row.features[column].is_nan() || donors.is_empty() {
+                continue;
+            }
+            let query = Sample { features: row.features.clone(), class: f64::NAN };
+            let neighbors = get_neighbors_with(&donors, &query, k, present_feature_distance);
+            let value = neighbors.iter().map(|n| n.class).sum::<f64>() / neighbors.len() as f64;
+            fills.push((i, column, value));
+        }
+    }
+
+    for (i, column, value) in fills {
+        dataset[i].features[column] = value;
+    }
+}
+
+/// Which per-column statistics a `Scaler` learns.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Scaling {
+    /// Rescale to 0..1 with `(min, max)`, as `normalize_dataset` does.
+    #[default]
+    MinMax,
+    /// Center and scale with `(mean, std)`, as `standardize_dataset` does.
+    Standard,
+    /// Center and scale with `(median, iqr)`, as `robust_scale` does.
+    Robust,
+}
+
+/// Feature scaling whose per-column parameters are learned once with `fit`
+/// and then applied to any number of datasets with `transform`. Fit on
+/// training data only, so held-out rows never influence the parameters.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Scaler {
+    scaling: Scaling,
+    params: Vec<(f64, f64)>,
+}
+
+impl Scaler {
+    /// A min-max scaler.
+    pub fn new() -> Scaler {
+        Scaler::default()
+    }
+
+    pub fn with_scaling(scaling: Scaling) -> Scaler {
+        Scaler {
+            scaling,
+            params: Vec::new(),
+        }
+    }
+
+    pub fn fit(&mut self, dataset: &[Sample]) {
+        self.params = match self.scaling {
+            Scaling::MinMax => dataset_minmax(dataset),
+            Scaling::Standard => dataset_mean_std(dataset),
+            Scaling::Robust => dataset_median_iqr(dataset),
+        };
+    }
+
+    pub fn transform(&self, dataset: &mut [Sample]) {
+        match self.scaling {
+            Scaling::MinMax => normalize_dataset(dataset, &self.params),
+            Scaling::Standard => standardize_dataset(dataset, &self.params),
+            Scaling::Robust => robust_scale(dataset, &self.params),
+        }
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    /// The per-column parameters learned by the last `fit`: `(min, max)`,
+    /// `(mean, std)` or `(median, iqr)` depending on the scaling.
+    pub fn params(&self) -> &[(f64, f64)] {
+        &self.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::load_csv;
+    use crate::distance::DistanceMetric;
+    use crate::knn::{k_nearest_neighbors, Voting};
+    use crate::validation::evaluate_algorithm;
+
+    fn sample(features: &[f64]) -> Sample {
+        Sample::new(features.to_vec(), "x")
+    }
+
+    fn column(dataset: &[Sample], i: usize) -> Vec<f64> {
+        dataset.iter().map(|row| row.features[i]).collect()
+    }
+
+    #[test]
+    fn maxabs_scale_bounds_columns_by_one_and_keeps_zero_columns() {
+        let mut dataset = vec![sample(&[2.0, 0.0, -8.0]), sample(&[-4.0, 0.0, 1.0]), sample(&[1.0, 0.0, 4.0])];
+        let maxabs = dataset_maxabs(&dataset);
+        assert_eq!(maxabs, [4.0, 0.0, 8.0]);
+        maxabs_scale(&mut dataset, &maxabs);
+
+        for i in [0, 2] {
+            let largest = column(&dataset, i).iter().map(|value| value.abs()).fold(0.0, f64::max);
+            assert_eq!(largest, 1.0);
+        }
+        assert_eq!(column(&dataset, 0), [0.5, -1.0, 0.25]);
+        assert_eq!(column(&dataset, 1), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn dataset_minmax_gives_one_range_per_column() {
+        let dataset = vec![sample(&[1.0, 5.0, -2.0]), sample(&[3.0, 4.0, -7.0]), sample(&[2.0, 6.0, 0.0])];
+        assert_eq!(dataset_minmax(&dataset), [(1.0, 3.0), (4.0, 6.0), (-7.0, 0.0)]);
+        assert!(dataset_minmax(&[]).is_empty());
+    }
+
+    #[test]
+    fn normalize_dataset_maps_a_constant_column_to_zero() {
+        // Every flower has the same sepal_width.
+        let mut dataset = vec![
+            sample(&[5.1, 3.0, 1.4, 0.2]),
+            sample(&[4.9, 3.0, 1.5, 0.2]),
+            sample(&[6.3, 3.0, 6.0, 2.5]),
+        ];
+        let minmax = dataset_minmax(&dataset);
+        normalize_dataset(&mut dataset, &minmax);
+
+        assert!(dataset.iter().flat_map(|row| &row.features).all(|value| !value.is_nan()));
+        assert_eq!(column(&dataset, 1), [0.0, 0.0, 0.0]);
+        assert_eq!(column(&dataset, 3), [0.0, 0.0, 1.0]);
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn standardized_columns_have_zero_mean_and_unit_std() {
+        let mut dataset = vec![
+            sample(&[5.1, 3.5, 1.4]),
+            sample(&[4.9, 3.0, 1.4]),
+            sample(&[6.3, 3.3, 6.0]),
+            sample(&[5.8, 2.7, 5.1]),
+        ];
+        let stats = dataset_mean_std(&dataset);
+        standardize_dataset(&mut dataset, &stats);
+
+        for (mean, std) in dataset_mean_std(&dataset) {
+            assert_close(mean, 0.0);
+            assert_close(std, 1.0);
+        }
+    }
+
+    #[test]
+    fn scaler_applies_parameters_fitted_on_another_dataset() {
+        let train = vec![sample(&[0.0, 10.0]), sample(&[4.0, 20.0])];
+        let mut scaler = Scaler::new();
+        scaler.fit(&train);
+        assert_eq!(scaler.params(), [(0.0, 4.0), (10.0, 20.0)]);
+
+        let mut test = vec![sample(&[2.0, 25.0]), sample(&[-4.0, 10.0])];
+        scaler.transform(&mut test);
+        assert_eq!(test[0].features, [0.5, 1.5]);
+        assert_eq!(test[1].features, [-1.0, 0.0]);
+
+        let mut standard = Scaler::with_scaling(Scaling::Standard);
+        standard.fit(&train);
+        let mut test = vec![sample(&[4.0, 10.0])];
+        standard.transform(&mut test);
+        assert_eq!(test[0].features, [1.0, -1.0]);
+    }
+
+    fn iris() -> Vec<Sample> {
+        load_csv(concat!(env!("CARGO_MANIFEST_DIR"), "/../iris.csv"), true, b',').unwrap()
+    }
+
+    #[test]
+    fn knn_impute_restores_a_blanked_value_from_its_neighbors() {
+        let mut dataset = iris();
+        // Line 62 of iris.csv, a versicolor with petal length 3.5.
+        let row = 60;
+        assert_eq!(dataset[row].features[2], 3.5);
+        dataset[row].features[2] = f64::NAN;
+        knn_impute(&mut dataset, 5);
+
+        assert!((dataset[row].features[2] - 3.42).abs() < 1e-9, "{}", dataset[row].features[2]);
+        assert!(dataset.iter().flat_map(|row| &row.features).all(|value| !value.is_nan()));
+    }
+
+    #[test]
+    fn robust_scale_keeps_inliers_spread_out_despite_an_outlier() {
+        let dataset: Vec<Sample> = [1.0, 2.0, 3.0, 4.0, 100.0].iter().map(|&v| sample(&[v])).collect();
+        let stats = dataset_median_iqr(&dataset);
+        assert_eq!(stats, [(3.0, 2.0)]);
+
+        let mut robust = dataset.clone();
+        robust_scale(&mut robust, &stats);
+        assert_eq!(column(&robust, 0)[..4], [-1.0, -0.5, 0.0, 0.5]);
+
+        let mut standard = dataset.clone();
+        let stats = dataset_mean_std(&standard);
+        standardize_dataset(&mut standard, &stats);
+        let robust_spread = robust[3].features[0] - robust[0].features[0];
+        let standard_spread = standard[3].features[0] - standard[0].features[0];
+        assert!(standard_spread < 0.1, "{}", standard_spread);
+        assert!(robust_spread > 10.0 * standard_spread);
+    }
+
+    #[test]
+    fn two_pca_components_keep_iris_accurate() {
+        let mut dataset = iris();
+        let model = pca_fit(&dataset, 2);
+        assert_eq!(model.components.len(), 2);
+        assert!(model.variances[0] > model.variances[1]);
+        pca_transform(&mut dataset, &model);
+        assert!(dataset.iter().all(|row| row.features.len() == 2));
+
+        let result = evaluate_algorithm(&dataset, k_nearest_neighbors, 5, 5, DistanceMetric::Euclidean, Voting::Majority, Some(1)).unwrap();
+        assert!(result.mean > 90.0, "{}", result.mean);
+    }
+
+    #[test]
+    fn label_encoder_round_trips_the_iris_labels() {
+        let dataset = iris();
+        let encoder = fit_labels(&dataset);
+        assert_eq!(encoder.classes(), ["Iris-setosa", "Iris-versicolor", "Iris-virginica"]);
+        assert_eq!(encoder.len(), 3);
+
+        for row in &dataset {
+            let id = encoder.encode(&row.class);
+            assert!(id < encoder.len());
+            assert_eq!(encoder.decode(id), row.class);
+        }
+        for id in 0..encoder.len() {
+            assert_eq!(encoder.encode(encoder.decode(id)), id);
+        }
+    }
+
+    #[test]
+    fn incremental_scaler_matches_dataset_mean_std() {
+        let dataset = iris();
+        let mut incremental = IncrementalScaler::new();
+        for chunk in dataset.chunks(40) {
+            for row in chunk {
+                incremental.update(row);
+            }
+        }
+        assert_eq!(incremental.count(), 150);
+
+        let expected = dataset_mean_std(&dataset);
+        assert_eq!(incremental.stats().len(), expected.len());
+        for (&(mean, std), &(expected_mean, expected_std)) in incremental.stats().iter().zip(&expected) {
+            assert_close(mean, expected_mean);
+            assert_close(std, expected_std);
+        }
+        assert_eq!(incremental.finish().scaling(), Scaling::Standard);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::data::Sample;
+use crate::distance::{distance, DistanceMetric};
+use crate::knn::{select_nearest, MeasuredFlower};
+
+/// SMOTE oversampling: `n_synthetic` new rows of `target_class`, each a
+/// random point on the line between a real row of that class and one of its
+/// `num_neighbors` nearest (Euclidean) same-class rows. Base rows are taken
+/// in turn, so every real row seeds about the same number of new ones. The
+/// same seed always gives the same rows. Returns only the synthetic rows;
+/// append them to the dataset to rebalance it.
+///
+/// Returns no rows if the class has fewer than two rows or `num_neighbors`
+/// is 0, since there is nothing to interpolate towards.
+pub fn smote(dataset: &[Sample], target_class: &str, num_neighbors: usize, n_synthetic: usize, seed: u64) -> Vec<Sample> {
+    let minority: Vec<&Sample> = dataset.iter().filter(|row| row.class == target_class).collect();
+    if minority.len() < 2 || num_neighbors == 0 {
+        return Vec::new();
+    }
+
+    // Each minority row's nearest other minority rows, by index.
+    let neighbors: Vec<Vec<usize>> = (0..minority.len())
+        .map(|i| {
+            let others = (0..minority.len())
+                .filter(|&j| j != i)
+                .map(|j| MeasuredFlower { distance: distance(DistanceMetric::Euclidean, minority[i], minority[j]), class: j })
+                .collect();
+            select_nearest(others, num_neighbors).into_iter().map(|n| n.class).collect()
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n_synthetic)
+        .map(|n| {
+            let base = n % minority.len();
+            let neighbor = minority[neighbors[base][rng.gen_range(0..neighbors[base].len())]];
+            let gap: f64 = rng.gen();
+            let features = minority[base].features.iter()
+                .zip(&neighbor.features)
+                .map(|(x, y)| x + gap * (y - x))
+                .collect();
+            Sample { features, class: target_class.to_string() }
+        })
+        .collect()
+}
+
+/// Randomly drops rows from every class larger than the smallest one until
+/// all classes have the smallest class's count. Rows of the smallest class
+/// are all kept, and kept rows stay in their original order. The same seed
+/// always drops the same rows.
+pub fn undersample_majority(dataset: &[Sample], seed: u64) -> Vec<Sample> {
+    let mut by_class: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, row) in dataset.iter().enumerate() {
+        by_class.entry(row.class.as_str()).or_default().push(i);
+    }
+    let Some(target) = by_class.values().map(Vec::len).min() else {
+        return Vec::new();
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut keep: Vec<usize> = Vec::new();
+    for mut rows in by_class.into_values() {
+        rows.shuffle(&mut rng);
+        rows.truncate(target);
+        keep.extend(rows);
+    }
+    keep.sort_unstable();
+    keep.into_iter().map(|i| dataset[i].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::euclidean_distance;
+
+    fn sample(features: &[f64], class: &str) -> Sample {
+        Sample::new(features.to_vec(), class)
+    }
+
+    #[test]
+    fn smote_interpolates_between_each_row_and_its_nearest_neighbor() {
+        let dataset = vec![
+            sample(&[0.0, 0.0], "rare"),
+            sample(&[3.0, 3.0], "common"),
+            sample(&[1.0, 0.0], "rare"),
+            sample(&[3.0, 2.0], "common"),
+            sample(&[0.0, 5.0], "rare"),
+            sample(&[2.0, 3.0], "common"),
+            sample(&[6.0, 6.0], "rare"),
+        ];
+        // Each rare row's nearest other rare row, in the order they appear.
+        let pairs = [(0, 2), (2, 0), (4, 0), (6, 4)];
+
+        let synthetic = smote(&dataset, "rare", 1, 12, 3);
+        assert_eq!(synthetic.len(), 12);
+        for (n, row) in synthetic.iter().enumerate() {
+            assert_eq!(row.class, "rare");
+            let (base, neighbor) = (&dataset[pairs[n % 4].0], &dataset[pairs[n % 4].1]);
+            let detour = euclidean_distance(base, row) + euclidean_distance(row, neighbor);
+            assert!((detour - euclidean_distance(base, neighbor)).abs() < 1e-9, "{:?} is off the segment", row.features);
+        }
+        assert_eq!(smote(&dataset, "rare", 1, 12, 3)[5].features, synthetic[5].features);
+        assert!(smote(&dataset[..2], "rare", 1, 5, 3).is_empty());
+    }
+
+    #[test]
+    fn undersample_majority_evens_out_classes_and_keeps_the_minority() {
+        let mut dataset = Vec::new();
+        for i in 0..20 {
+            dataset.push(sample(&[i as f64], "big"));
+            if i % 4 == 0 {
+                dataset.push(sample(&[-(i as f64)], "small"));
+            }
+            if i % 2 == 0 {
+                dataset.push(sample(&[100.0 + i as f64], "medium"));
+            }
+        }
+        let balanced = undersample_majority(&dataset, 9);
+
+        for class in ["big", "small", "medium"] {
+            assert_eq!(balanced.iter().filter(|row| row.class == class).count(), 5, "{}", class);
+        }
+        let small = |rows: &[Sample]| -> Vec<Vec<f64>> {
+            rows.iter().filter(|row| row.class == "small").map(|row| row.features.clone()).collect()
+        };
+        assert_eq!(small(&balanced), small(&dataset));
+        assert!(balanced.iter().all(|row| dataset.iter().any(|original| original.features == row.features)));
+    }
+}
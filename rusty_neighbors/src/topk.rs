@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::knn::{compare_distances, MeasuredFlower};
+
+/// A neighbor ordered by distance alone, so any label type fits in the heap.
+pub(crate) struct ByDistance<L>(pub(crate) MeasuredFlower<L>);
+
+impl<L> PartialEq for ByDistance<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl<L> Eq for ByDistance<L> {}
+
+impl<L> PartialOrd for ByDistance<L> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<L> Ord for ByDistance<L> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_distances(self.0.distance, other.0.distance)
+    }
+}
+
+/// Collects the `k` nearest of a stream of neighbors in O(k) memory. A
+/// max-heap keeps the farthest kept neighbor on top, so each new one either
+/// replaces it or is dropped. NaN distances rank after every real one.
+pub struct TopK<L = String> {
+    k: usize,
+    heap: BinaryHeap<ByDistance<L>>,
+}
+
+impl<L> TopK<L> {
+    pub fn new(k: usize) -> TopK<L> {
+        TopK {
+            k,
+            heap: BinaryHeap::with_capacity(k),
+        }
+    }
+
+    /// Keeps `neighbor` if it is among the `k` nearest seen so far.
+    pub fn push(&mut self, neighbor: MeasuredFlower<L>) {
+        if self.heap.len() < self.k {
+            self.heap.push(ByDistance(neighbor));
+        } else if let Some(mut farthest) = self.heap.peek_mut() {
+            if compare_distances(neighbor.distance, farthest.0.distance).is_lt() {
+                *farthest = ByDistance(neighbor);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The kept neighbors, nearest first.
+    pub fn into_sorted_vec(self) -> Vec<MeasuredFlower<L>> {
+        self.heap.into_sorted_vec().into_iter().map(|neighbor| neighbor.0).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    use super::*;
+
+    #[test]
+    fn keeps_the_k_smallest_of_a_shuffled_stream() {
+        let mut distances: Vec<usize> = (0..100).collect();
+        distances.shuffle(&mut StdRng::seed_from_u64(71));
+
+        let mut top = TopK::new(5);
+        assert!(top.is_empty());
+        for &d in &distances {
+            top.push(MeasuredFlower { distance: d as f64, class: d });
+        }
+        assert_eq!(top.len(), 5);
+        let kept: Vec<usize> = top.into_sorted_vec().into_iter().map(|n| n.class).collect();
+        assert_eq!(kept, [0, 1, 2, 3, 4]);
+
+        let mut with_nan = TopK::new(2);
+        for d in [f64::NAN, 3.0, 1.0] {
+            with_nan.push(MeasuredFlower { distance: d, class: () });
+        }
+        assert_eq!(with_nan.into_sorted_vec().iter().map(|n| n.distance).collect::<Vec<_>>(), [1.0, 3.0]);
+    }
+}
@@ -0,0 +1,671 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::data::Sample;
+use crate::distance::DistanceMetric;
+use crate::error::KnnError;
+use crate::knn::{k_nearest_neighbors, predict_classification, Voting};
+use crate::metrics::accuracy_metric;
+use crate::preprocessing::Scaler;
+
+/// Shuffles `dataset` in place. The same seed always gives the same order.
+pub fn shuffle_dataset<L>(dataset: &mut [Sample<L>], seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    dataset.shuffle(&mut rng);
+}
+
+/// Shuffles `dataset` with `seed` and splits off `test_ratio` of the rows
+/// (rounded) as a test set. Returns `(train, test)`.
+///
+/// Panics unless `0.0 < test_ratio < 1.0`.
+pub fn train_test_split<L>(mut dataset: Vec<Sample<L>>, test_ratio: f64, seed: u64) -> (Vec<Sample<L>>, Vec<Sample<L>>) {
+    assert!(test_ratio > 0.0 && test_ratio < 1.0, "test_ratio must be between 0 and 1, got {}", test_ratio);
+    shuffle_dataset(&mut dataset, seed);
+
+    let n_test = (dataset.len() as f64 * test_ratio).round() as usize;
+    let train = dataset.split_off(n_test);
+    (train, dataset)
+}
+
+/// Deals the rows out to `n_folds` folds in turn, so row `i` lands in fold
+/// `i % n_folds`.
+///
+/// Fails with `InvalidFolds` for fewer than two folds.
+pub fn cross_validation_split(dataset: &[Sample], n_folds: usize) -> Result<Vec<Vec<Sample>>, KnnError> {
+    if n_folds < 2 {
+        return Err(KnnError::InvalidFolds(n_folds));
+    }
+    let mut folds = vec![Vec::new(); n_folds];
+    for (i, row) in dataset.iter().enumerate() {
+        folds[i % n_folds].push(row.clone());
+    }
+    Ok(folds)
+}
+
+/// Like `cross_validation_split`, but deals each class out across the folds
+/// separately so every fold keeps roughly the dataset's class proportions.
+///
+/// Fails with `InvalidFolds` for fewer than two folds.
+pub fn stratified_split(dataset: &[Sample], n_folds: usize) -> Result<Vec<Vec<Sample>>, KnnError> {
+    if n_folds < 2 {
+        return Err(KnnError::InvalidFolds(n_folds));
+    }
+    let mut by_class: BTreeMap<&str, Vec<&Sample>> = BTreeMap::new();
+    for row in dataset {
+        by_class.entry(row.class.as_str()).or_default().push(row);
+    }
+
+    // Keep counting across classes so fold sizes stay balanced too.
+    let mut folds = vec![Vec::new(); n_folds];
+    for (i, row) in by_class.into_values().flatten().enumerate() {
+        folds[i % n_folds].push(row.clone());
+    }
+    Ok(folds)
+}
+
+/// One fold's accuracy and how long it took to scale, train and predict.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoldResult {
+    pub score: f64,
+    pub duration: Duration,
+}
+
+/// Summary of per-fold accuracy scores.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValResult {
+    pub mean: f64,
+    /// Population standard deviation of the fold scores.
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub scores: Vec<f64>,
+    /// Per-fold scores with timings, in the same order as `scores`. Empty for
+    /// results built with `from_scores`.
+    pub folds: Vec<FoldResult>,
+}
+
+impl CrossValResult {
+    /// Summarizes timed folds, keeping them in `folds`.
+    pub fn from_folds(folds: Vec<FoldResult>) -> CrossValResult {
+        let scores = folds.iter().map(|fold| fold.score).collect();
+        CrossValResult {
+            folds,
+            ..CrossValResult::from_scores(scores)
+        }
+    }
+
+    pub fn from_scores(scores: Vec<f64>) -> CrossValResult {
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+        CrossValResult {
+            mean,
+            std_dev: variance.sqrt(),
+            min: scores.iter().copied().fold(f64::INFINITY, f64::min),
+            max: scores.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            scores,
+            folds: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for CrossValResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Mean Accuracy: {:.3}% (std dev {:.3}, min {:.3}%, max {:.3}%, {} folds)",
+            self.mean, self.std_dev, self.min, self.max, self.scores.len()
+        )
+    }
+}
+
+/// A train-then-predict function such as `k_nearest_neighbors`.
+pub type Algorithm = fn(&[Sample], &[Sample], usize, DistanceMetric, Voting) -> Result<Vec<String>, KnnError>;
+
+/// Cross-validated accuracy of `algorithm`, one score per fold. Features are
+/// min-max scaled per fold using ranges fitted on that fold's training rows.
+/// With a `seed` the rows are shuffled before being split into folds.
+/// Folds left empty because `n_folds` exceeds the number of rows are
+/// skipped, so the result can have fewer than `n_folds` scores.
+///
+/// Fails on an empty dataset, a dataset too small to leave any training
+/// rows, fewer than two folds, a `num_neighbors` of 0 or rows with differing
+/// numbers of features, and with the first error `algorithm` returns on any
+/// fold.
+pub fn evaluate_algorithm(
+    dataset: &[Sample],
+    algorithm: Algorithm,
+    n_folds: usize,
+    num_neighbors: usize,
+    metric: DistanceMetric,
+    voting: Voting,
+    seed: Option<u64>,
+) -> Result<CrossValResult, KnnError> {
+    check_dataset(dataset, n_folds)?;
+    if num_neighbors == 0 {
+        return Err(KnnError::InvalidK(num_neighbors));
+    }
+    let folds = fold_indices(dataset.len(), n_folds, seed);
+    Ok(CrossValResult::from_folds(fold_scores(dataset, &folds, algorithm, num_neighbors, metric, voting)?))
+}
+
+/// The input checks shared by `evaluate_algorithm` and
+/// `nested_cross_validation`.
+fn check_dataset(dataset: &[Sample], n_folds: usize) -> Result<(), KnnError> {
+    check_rows(dataset)?;
+    if n_folds < 2 {
+        return Err(KnnError::InvalidFolds(n_folds));
+    }
+    Ok(())
+}
+
+/// Fails on an empty dataset or rows with differing numbers of features.
+fn check_rows(dataset: &[Sample]) -> Result<(), KnnError> {
+    let Some(first) = dataset.first() else {
+        return Err(KnnError::EmptyDataset);
+    };
+    let expected = first.features.len();
+    if let Some(row) = dataset.iter().find(|row| row.features.len() != expected) {
+        return Err(KnnError::DimensionMismatch { expected, found: row.features.len() });
+    }
+    Ok(())
+}
+
+/// Row indices split into `n_folds` folds, laid out exactly as
+/// `cross_validation_split` would, so each fold only clones the rows it
+/// actually trains and tests on. With a `seed` the rows are shuffled first.
+fn fold_indices(n_rows: usize, n_folds: usize, seed: Option<u64>) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..n_rows).collect();
+    if let Some(seed) = seed {
+        order.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    let mut folds = vec![Vec::new(); n_folds];
+    for (i, &row) in order.iter().enumerate() {
+        folds[i % n_folds].push(row);
+    }
+    folds
+}
+
+/// Timed accuracy of `algorithm` on each non-empty fold of row indices,
+/// trained on the rest with train-only scaling. With the `parallel` feature
+/// the folds run across threads; results are always in fold order. Fails
+/// with the first error `algorithm` returns.
+#[cfg(feature = "parallel")]
+fn fold_scores(
+    dataset: &[Sample],
+    folds: &[Vec<usize>],
+    algorithm: Algorithm,
+    num_neighbors: usize,
+    metric: DistanceMetric,
+    voting: Voting,
+) -> Result<Vec<FoldResult>, KnnError> {
+    use rayon::prelude::*;
+
+    (0..folds.len())
+        .into_par_iter()
+        .filter(|&i| !folds[i].is_empty())
+        .map(|i| score_fold(dataset, folds, i, algorithm, num_neighbors, metric, voting))
+        .collect()
+}
+
+/// Timed accuracy of `algorithm` on each non-empty fold of row indices,
+/// trained on the rest with train-only scaling. With the `parallel` feature
+/// the folds run across threads; results are always in fold order. Fails
+/// with the first error `algorithm` returns.
+#[cfg(not(feature = "parallel"))]
+fn fold_scores(
+    dataset: &[Sample],
+    folds: &[Vec<usize>],
+    algorithm: Algorithm,
+    num_neighbors: usize,
+    metric: DistanceMetric,
+    voting: Voting,
+) -> Result<Vec<FoldResult>, KnnError> {
+    // More folds than rows leaves some empty, with no accuracy to measure.
+    (0..folds.len())
+        .filter(|&i| !folds[i].is_empty())
+        .map(|i| score_fold(dataset, folds, i, algorithm, num_neighbors, metric, voting))
+        .collect()
+}
+
+/// Accuracy on fold `i` of a model trained on every other fold, timed.
+/// Fails with `EmptyTraining` when the other folds are all empty, as with a
+/// one-row dataset.
+fn score_fold(
+    dataset: &[Sample],
+    folds: &[Vec<usize>],
+    i: usize,
+    algorithm: Algorithm,
+    num_neighbors: usize,
+    metric: DistanceMetric,
+    voting: Voting,
+) -> Result<FoldResult, KnnError> {
+    let start = Instant::now();
+    let mut test_set: Vec<Sample> = folds[i].iter().map(|&row| dataset[row].clone()).collect();
+    let mut train_set: Vec<Sample> = folds.iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .flat_map(|(_, fold)| fold.iter().map(|&row| dataset[row].clone()))
+        .collect();
+    if train_set.is_empty() {
+        return Err(KnnError::EmptyTraining);
+    }
+
+    // Scale with ranges from the training folds only so the held-out
+    // fold doesn't leak into training.
+    let mut scaler = Scaler::new();
+    scaler.fit(&train_set);
+    scaler.transform(&mut train_set);
+    scaler.transform(&mut test_set);
+
+    let predicted = algorithm(&train_set, &test_set, num_neighbors, metric, voting)?;
+    let actual: Vec<String> = test_set.iter().map(|row| row.class.clone()).collect();
+    Ok(FoldResult {
+        score: accuracy_metric(&actual, &predicted),
+        duration: start.elapsed(),
+    })
+}
+
+/// Repeated stratified k-fold: `repeats` times, reshuffles the rows, deals
+/// each class out across `n_folds` folds as `stratified_split` does, and
+/// scores `k_nearest_neighbors` (Euclidean, majority vote) on every fold.
+/// The result aggregates all `n_folds * repeats` scores, less any folds left
+/// empty because `n_folds` exceeds the number of rows.
+///
+/// Fails as `evaluate_algorithm` does, and with `InvalidRepeats` when
+/// `repeats` is 0.
+pub fn repeated_cross_validation(dataset: &[Sample], n_folds: usize, repeats: usize, seed: u64, num_neighbors: usize) -> Result<CrossValResult, KnnError> {
+    check_dataset(dataset, n_folds)?;
+    if num_neighbors == 0 {
+        return Err(KnnError::InvalidK(num_neighbors));
+    }
+    if repeats == 0 {
+        return Err(KnnError::InvalidRepeats(repeats));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut results = Vec::new();
+
+    for _ in 0..repeats {
+        let mut order: Vec<usize> = (0..dataset.len()).collect();
+        order.shuffle(&mut rng);
+
+        let mut by_class: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for row in order {
+            by_class.entry(dataset[row].class.as_str()).or_default().push(row);
+        }
+        let mut folds = vec![Vec::new(); n_folds];
+        for (i, row) in by_class.into_values().flatten().enumerate() {
+            folds[i % n_folds].push(row);
+        }
+
+        results.extend(fold_scores(dataset, &folds, k_nearest_neighbors, num_neighbors, DistanceMetric::Euclidean, Voting::Majority)?);
+    }
+    Ok(CrossValResult::from_folds(results))
+}
+
+/// Leave-one-out accuracy: every row in turn is predicted by a model trained
+/// on all the others, with the same train-only scaling `evaluate_algorithm`
+/// uses. Returns a percentage like `accuracy_metric`.
+///
+/// Fails on an empty dataset, a single row (which leaves nothing to train
+/// on), rows with differing numbers of features, or as
+/// `predict_classification` does for any row.
+pub fn leave_one_out(dataset: &[Sample], num_neighbors: usize, metric: DistanceMetric) -> Result<f64, KnnError> {
+    check_rows(dataset)?;
+    if dataset.len() < 2 {
+        return Err(KnnError::EmptyTraining);
+    }
+    let mut predicted = Vec::new();
+
+    for i in 0..dataset.len() {
+        let mut train_set = [&dataset[..i], &dataset[i + 1..]].concat();
+        let mut test_row = [dataset[i].clone()];
+
+        let mut scaler = Scaler::new();
+        scaler.fit(&train_set);
+        scaler.transform(&mut train_set);
+        scaler.transform(&mut test_row);
+
+        let prediction = predict_classification(&train_set, &test_row[0], num_neighbors, metric, Voting::Majority)?;
+        predicted.push(prediction);
+    }
+
+    let actual: Vec<String> = dataset.iter().map(|row| row.class.clone()).collect();
+    Ok(accuracy_metric(&actual, &predicted))
+}
+
+/// Cross-validates `k_nearest_neighbors` with Euclidean distance and majority
+/// voting for every k in `k_values`, returning the k with the highest mean
+/// accuracy and that accuracy. Ties go to the earliest k in the list. Fails
+/// as `evaluate_algorithm` does for any k.
+///
+/// Panics if `k_values` is empty.
+pub fn grid_search_k(dataset: &[Sample], k_values: &[usize], n_folds: usize) -> Result<(usize, f64), KnnError> {
+    assert!(!k_values.is_empty(), "grid_search_k needs at least one k");
+    let mut best = (k_values[0], f64::NEG_INFINITY);
+
+    for &k in k_values {
+        let result = evaluate_algorithm(dataset, k_nearest_neighbors, n_folds, k, DistanceMetric::Euclidean, Voting::Majority, None)?;
+        if result.mean > best.1 {
+            best = (k, result.mean);
+        }
+    }
+    Ok(best)
+}
+
+/// Like `grid_search_k`, but tries every combination of k in `k_values` and
+/// metric in `metrics`, returning the best pair and its mean accuracy. Ties
+/// go to the earliest k, then the earliest metric.
+///
+/// Panics if `k_values` or `metrics` is empty.
+pub fn grid_search(dataset: &[Sample], k_values: &[usize], metrics: &[DistanceMetric], n_folds: usize) -> Result<(usize, DistanceMetric, f64), KnnError> {
+    assert!(!k_values.is_empty(), "grid_search needs at least one k");
+    assert!(!metrics.is_empty(), "grid_search needs at least one metric");
+    let mut best = (k_values[0], metrics[0], f64::NEG_INFINITY);
+
+    for &k in k_values {
+        for &metric in metrics {
+            let result = evaluate_algorithm(dataset, k_nearest_neighbors, n_folds, k, metric, Voting::Majority, None)?;
+            if result.mean > best.2 {
+                best = (k, metric, result.mean);
+            }
+        }
+    }
+    Ok(best)
+}
+
+/// Nested cross-validation: for each of `outer_folds` folds, k is tuned with
+/// `grid_search_k` over `k_values` using `inner_folds`-fold cross-validation
+/// on the other folds only, then the tuned model is scored on the held-out
+/// fold. Because the held-out rows never influence the choice of k, the
+/// scores estimate how the whole tune-then-train procedure generalizes,
+/// without the optimism of reporting the best grid search score. Euclidean
+/// distance and majority voting throughout; empty outer folds are skipped.
+///
+/// Fails as `evaluate_algorithm` does. Panics if `k_values` is empty.
+pub fn nested_cross_validation(dataset: &[Sample], k_values: &[usize], outer_folds: usize, inner_folds: usize) -> Result<CrossValResult, KnnError> {
+    check_dataset(dataset, outer_folds)?;
+    let folds = fold_indices(dataset.len(), outer_folds, None);
+
+    let mut results = Vec::new();
+    for (i, fold) in folds.iter().enumerate() {
+        if fold.is_empty() {
+            continue;
+        }
+        let train_set: Vec<Sample> = folds.iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .flat_map(|(_, fold)| fold.iter().map(|&row| dataset[row].clone()))
+            .collect();
+        let (best_k, _) = grid_search_k(&train_set, k_values, inner_folds)?;
+        results.push(score_fold(dataset, &folds, i, k_nearest_neighbors, best_k, DistanceMetric::Euclidean, Voting::Majority)?);
+    }
+    Ok(CrossValResult::from_folds(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::load_csv;
+
+    fn sample(features: &[f64], class: &str) -> Sample {
+        Sample::new(features.to_vec(), class)
+    }
+
+    /// Six rows in two well-separated classes.
+    fn six_rows() -> Vec<Sample> {
+        vec![
+            sample(&[0.0, 0.0], "a"),
+            sample(&[0.0, 1.0], "a"),
+            sample(&[1.0, 0.0], "a"),
+            sample(&[9.0, 9.0], "b"),
+            sample(&[9.0, 8.0], "b"),
+            sample(&[8.0, 9.0], "b"),
+        ]
+    }
+
+    fn evaluate(dataset: &[Sample], n_folds: usize, num_neighbors: usize) -> Result<CrossValResult, KnnError> {
+        evaluate_algorithm(dataset, k_nearest_neighbors, n_folds, num_neighbors, DistanceMetric::Euclidean, Voting::Majority, None)
+    }
+
+    #[test]
+    fn evaluate_algorithm_checks_its_inputs() {
+        let mut ragged = six_rows();
+        ragged[4].features.pop();
+        assert!(matches!(evaluate(&[], 2, 1), Err(KnnError::EmptyDataset)));
+        assert!(matches!(evaluate(&six_rows(), 1, 1), Err(KnnError::InvalidFolds(1))));
+        assert!(matches!(evaluate(&six_rows(), 2, 0), Err(KnnError::InvalidK(0))));
+        assert!(matches!(evaluate(&ragged, 2, 1), Err(KnnError::DimensionMismatch { expected: 2, found: 1 })));
+
+        let minkowski = evaluate_algorithm(&six_rows(), k_nearest_neighbors, 2, 1, DistanceMetric::Minkowski(-1.0), Voting::Majority, None);
+        assert!(matches!(minkowski, Err(KnnError::InvalidMinkowskiP(p)) if p == -1.0));
+        let kernel = evaluate_algorithm(&six_rows(), k_nearest_neighbors, 2, 1, DistanceMetric::Euclidean, Voting::Kernel { bandwidth: 0.0 }, None);
+        assert!(matches!(kernel, Err(KnnError::InvalidBandwidth(bandwidth)) if bandwidth == 0.0));
+    }
+
+    #[test]
+    fn evaluate_algorithm_skips_empty_folds() {
+        let dataset = &six_rows()[..3];
+        let result = evaluate(dataset, 10, 1).unwrap();
+        assert_eq!(result.scores.len(), 3);
+        assert!(result.scores.iter().all(|score| !score.is_nan()));
+        assert!(!result.mean.is_nan());
+
+        assert!(matches!(evaluate(&six_rows()[..1], 2, 1), Err(KnnError::EmptyTraining)));
+    }
+
+    #[test]
+    fn repeated_cross_validation_collects_every_fold() {
+        let result = repeated_cross_validation(&six_rows(), 3, 4, 7, 1).unwrap();
+        assert_eq!(result.scores.len(), 3 * 4);
+        assert_eq!(result.folds.len(), 3 * 4);
+
+        assert!(matches!(repeated_cross_validation(&six_rows(), 0, 4, 7, 1), Err(KnnError::InvalidFolds(0))));
+        assert!(matches!(repeated_cross_validation(&six_rows(), 3, 4, 7, 0), Err(KnnError::InvalidK(0))));
+        assert!(matches!(repeated_cross_validation(&six_rows(), 3, 0, 7, 1), Err(KnnError::InvalidRepeats(0))));
+        assert!(matches!(repeated_cross_validation(&[], 3, 4, 7, 1), Err(KnnError::EmptyDataset)));
+    }
+
+    #[test]
+    fn leave_one_out_predicts_each_row_once() {
+        // Only the lone "b" row is misclassified, so 4 of 5 passes are right.
+        let dataset = vec![
+            sample(&[0.0, 0.0], "a"),
+            sample(&[0.0, 1.0], "a"),
+            sample(&[1.0, 0.0], "a"),
+            sample(&[1.0, 1.0], "a"),
+            sample(&[2.0, 2.0], "b"),
+        ];
+        assert_eq!(leave_one_out(&dataset, 1, DistanceMetric::Euclidean).unwrap(), 80.0);
+        assert_eq!(leave_one_out(&six_rows(), 1, DistanceMetric::Euclidean).unwrap(), 100.0);
+
+        assert!(matches!(leave_one_out(&dataset[..1], 1, DistanceMetric::Euclidean), Err(KnnError::EmptyTraining)));
+        assert!(matches!(leave_one_out(&dataset, 0, DistanceMetric::Euclidean), Err(KnnError::InvalidK(0))));
+    }
+
+    #[test]
+    fn evaluate_algorithm_propagates_fold_errors() {
+        // Two folds leave three training rows per fold.
+        assert!(matches!(evaluate(&six_rows(), 2, 4), Err(KnnError::KTooLarge { k: 4, n_rows: 3 })));
+
+        let mut with_nan = six_rows();
+        with_nan[0].features[0] = f64::NAN;
+        assert!(matches!(evaluate(&with_nan, 2, 3), Err(KnnError::NanDistance)));
+
+        assert_eq!(evaluate(&six_rows(), 2, 1).unwrap().mean, 100.0);
+    }
+
+    /// Labels each test row by whether its scaled features landed in 0..1.
+    fn label_by_range(_: &[Sample], test: &[Sample], _: usize, _: DistanceMetric, _: Voting) -> Result<Vec<String>, KnnError> {
+        Ok(test.iter()
+            .map(|row| if row.features.iter().all(|v| (0.0..=1.0).contains(v)) { "in" } else { "out" }.to_string())
+            .collect())
+    }
+
+    #[test]
+    fn score_fold_scales_the_test_fold_with_training_ranges() {
+        // Held out alone, 0 and 10 fall outside the other two rows' range and
+        // 5 falls inside it. Ranges fitted on every row would put all three in
+        // 0..1.
+        let dataset = vec![sample(&[0.0], "out"), sample(&[10.0], "out"), sample(&[5.0], "in")];
+        let folds = fold_indices(dataset.len(), 3, None);
+        for i in 0..3 {
+            let fold = score_fold(&dataset, &folds, i, label_by_range, 1, DistanceMetric::Euclidean, Voting::Majority).unwrap();
+            assert_eq!(fold.score, 100.0, "fold {}", i);
+        }
+    }
+
+    #[test]
+    fn stratified_split_keeps_class_ratios_in_every_fold() {
+        let mut dataset: Vec<Sample> = (0..90).map(|i| sample(&[i as f64], "a")).collect();
+        dataset.extend((0..10).map(|i| sample(&[i as f64], "b")));
+        let folds = stratified_split(&dataset, 5).unwrap();
+
+        assert_eq!(folds.iter().map(Vec::len).sum::<usize>(), dataset.len());
+        for fold in &folds {
+            let minority = fold.iter().filter(|row| row.class == "b").count() as f64 / fold.len() as f64;
+            assert!((minority - 0.1).abs() <= 0.02, "minority share {}", minority);
+        }
+        assert!(matches!(stratified_split(&dataset, 0), Err(KnnError::InvalidFolds(0))));
+        assert!(matches!(stratified_split(&dataset, 1), Err(KnnError::InvalidFolds(1))));
+    }
+
+    #[test]
+    fn shuffle_dataset_is_reproducible_per_seed() {
+        let dataset: Vec<Sample> = (0..20).map(|i| sample(&[i as f64], "a")).collect();
+        let shuffled = |seed| {
+            let mut rows = dataset.clone();
+            shuffle_dataset(&mut rows, seed);
+            rows
+        };
+        assert_eq!(shuffled(1), shuffled(1));
+        assert_ne!(shuffled(1), shuffled(2));
+        assert_ne!(shuffled(1), dataset);
+    }
+
+    #[test]
+    fn train_test_split_partitions_the_rows() {
+        let dataset: Vec<Sample> = (0..50).map(|i| sample(&[i as f64], "a")).collect();
+        let (train, test) = train_test_split(dataset, 0.2, 3);
+        assert_eq!((train.len(), test.len()), (40, 10));
+
+        let mut ids: Vec<f64> = train.iter().chain(&test).map(|row| row.features[0]).collect();
+        ids.sort_by(f64::total_cmp);
+        ids.dedup();
+        assert_eq!(ids.len(), 50);
+    }
+
+    #[test]
+    fn from_scores_summarizes_known_scores() {
+        let result = CrossValResult::from_scores(vec![90.0, 80.0, 100.0, 70.0]);
+        assert_eq!(result.mean, 85.0);
+        assert!((result.std_dev - 125.0_f64.sqrt()).abs() < 1e-12);
+        assert_eq!((result.min, result.max), (70.0, 100.0));
+        assert!(result.folds.is_empty());
+    }
+
+    fn iris() -> Vec<Sample> {
+        load_csv(concat!(env!("CARGO_MANIFEST_DIR"), "/../iris.csv"), true, b',').unwrap()
+    }
+
+    #[test]
+    fn grid_search_k_picks_a_listed_k_on_iris() {
+        let k_values = [1, 3, 5, 7];
+        let (k, accuracy) = grid_search_k(&iris(), &k_values, 5).unwrap();
+        assert!(k_values.contains(&k));
+        assert!(accuracy > 90.0 && accuracy <= 100.0, "{}", accuracy);
+    }
+
+    #[test]
+    fn cross_validation_split_deals_rows_out_in_turn() {
+        let folds = cross_validation_split(&six_rows(), 4).unwrap();
+        assert_eq!(folds.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1, 1]);
+        assert_eq!(folds[1][1].features, six_rows()[5].features);
+        assert!(matches!(cross_validation_split(&six_rows(), 0), Err(KnnError::InvalidFolds(0))));
+    }
+
+    #[test]
+    fn fold_indices_score_like_cloned_folds() {
+        let dataset = iris();
+        let folds = cross_validation_split(&dataset, 5).unwrap();
+        let mut expected = Vec::new();
+        for (i, fold) in folds.iter().enumerate() {
+            let mut train_set: Vec<Sample> = folds.iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .flat_map(|(_, other)| other.clone())
+                .collect();
+            let mut test_set = fold.clone();
+            let mut scaler = Scaler::new();
+            scaler.fit(&train_set);
+            scaler.transform(&mut train_set);
+            scaler.transform(&mut test_set);
+
+            let predicted = k_nearest_neighbors(&train_set, &test_set, 5, DistanceMetric::Euclidean, Voting::Majority).unwrap();
+            let actual: Vec<String> = test_set.iter().map(|row| row.class.clone()).collect();
+            expected.push(accuracy_metric(&actual, &predicted));
+        }
+        assert_eq!(evaluate(&dataset, 5, 5).unwrap().scores, expected);
+    }
+
+    #[test]
+    fn fold_scores_match_scoring_each_fold_in_turn() {
+        let dataset = iris();
+        let folds = fold_indices(dataset.len(), 5, Some(68));
+        let scores: Vec<f64> = fold_scores(&dataset, &folds, k_nearest_neighbors, 3, DistanceMetric::Manhattan, Voting::Majority)
+            .unwrap()
+            .iter()
+            .map(|fold| fold.score)
+            .collect();
+        let one_by_one: Vec<f64> = (0..folds.len())
+            .map(|i| score_fold(&dataset, &folds, i, k_nearest_neighbors, 3, DistanceMetric::Manhattan, Voting::Majority).unwrap().score)
+            .collect();
+        assert_eq!(scores, one_by_one);
+    }
+
+    #[test]
+    fn evaluate_algorithm_times_every_fold() {
+        let dataset = iris();
+        let start = Instant::now();
+        let result = evaluate(&dataset, 5, 3).unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(result.folds.len(), 5);
+        assert_eq!(result.folds.iter().map(|fold| fold.score).collect::<Vec<_>>(), result.scores);
+        // Each fold is timed inside the call, so none can take longer.
+        assert!(result.folds.iter().all(|fold| fold.duration <= elapsed));
+    }
+
+    #[test]
+    fn grid_search_returns_a_grid_pair_with_its_own_accuracy() {
+        let dataset = iris();
+        let k_values = [1, 5, 9];
+        let metrics = [DistanceMetric::Euclidean, DistanceMetric::Manhattan, DistanceMetric::Chebyshev];
+        let (k, metric, accuracy) = grid_search(&dataset, &k_values, &metrics, 5).unwrap();
+        assert!(k_values.contains(&k));
+        assert!(metrics.contains(&metric));
+
+        let direct = evaluate_algorithm(&dataset, k_nearest_neighbors, 5, k, metric, Voting::Majority, None).unwrap();
+        assert_eq!(accuracy, direct.mean);
+        for &other_k in &k_values {
+            for &other_metric in &metrics {
+                let other = evaluate_algorithm(&dataset, k_nearest_neighbors, 5, other_k, other_metric, Voting::Majority, None).unwrap();
+                assert!(other.mean <= accuracy);
+            }
+        }
+    }
+
+    #[test]
+    fn nested_cross_validation_scores_each_outer_fold() {
+        let result = nested_cross_validation(&iris(), &[1, 3, 5], 4, 3).unwrap();
+        assert_eq!(result.scores.len(), 4);
+        assert_eq!(result.folds.len(), 4);
+        assert!(result.scores.iter().all(|&score| (0.0..=100.0).contains(&score)));
+        assert!(result.mean > 80.0, "{}", result.mean);
+
+        let result = nested_cross_validation(&six_rows(), &[1], 3, 2).unwrap();
+        assert_eq!(result.scores.len(), 3);
+    }
+}